@@ -0,0 +1,274 @@
+//! Configuration files.
+//!
+//! A config file is a TOML table whose keys are the long names of the command line
+//! options, e.g. `recursive = true` or `format = "json"`. Named profiles live in
+//! `[profile.<name>]` tables and are applied on top of the rest of the file when they
+//! are selected with `--profile <name>`.
+//!
+//! Options are applied in this order, later ones replacing the values of earlier ones:
+//! the user config, the project config, the selected profile and the command line.
+//! Flags set in a config file are turned off on the command line with `--no-<flag>`.
+
+use crate::{Args, Error, Result};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueSource};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Name of the project config file, searched for in the counted directory and its parents.
+pub const CONFIG_FILE: &str = "lc.toml";
+
+/// A parsed config file.
+struct Config {
+    path: PathBuf,
+    table: Table,
+}
+
+/// Parses the command line arguments, including the options set by config files.
+pub fn parse_args() -> Result<Args> {
+    parse_args_from(std::env::args_os().collect(), |args| {
+        [
+            user_config_path(),
+            project_config_path(args.file_paths.first().map_or(".", String::as_str)),
+        ]
+        .into_iter()
+        .flatten()
+        .map(read_config)
+        .collect()
+    })
+}
+
+/// Parses `cli` on top of the config files returned by `load_configs`, from the least to
+/// the most specific one.
+fn parse_args_from(
+    cli: Vec<OsString>,
+    load_configs: impl FnOnce(&Args) -> Result<Vec<Config>>,
+) -> Result<Args> {
+    let command = Args::command();
+    let (cli, negated) = split_negations(&command, cli);
+    let matches = command.clone().get_matches_from(&cli);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if args.no_config {
+        return Ok(args);
+    }
+
+    let configs = load_configs(&args)?;
+
+    // Every option keeps the value of the last layer that sets it
+    let mut options = BTreeMap::new();
+    for config in &configs {
+        add_options(&mut options, &command, config, &config.table)?;
+    }
+    if let Some(name) = &args.profile {
+        // The project config is the more specific one, so its profiles come first
+        let (config, table) = configs
+            .iter()
+            .rev()
+            .find_map(|config| profile(config, name).map(|table| (config, table)))
+            .ok_or_else(|| Error::LcUnknownProfileError(name.clone()))?;
+        add_options(&mut options, &command, config, table?)?;
+    }
+    options.retain(|long, _| {
+        !negated.contains(long) && !given_on_command_line(&command, &matches, long)
+    });
+    if options.is_empty() {
+        return Ok(args);
+    }
+
+    let mut argv = vec![cli[0].clone()];
+    for (long, (config, value)) in options {
+        argv.extend(option_to_args(config, &long, value)?);
+    }
+    argv.extend(cli.into_iter().skip(1));
+    Ok(Args::parse_from(argv))
+}
+
+/// Removes the `--no-<FLAG>` arguments, which turn off a flag set in a config file, from
+/// the command line and returns the names of the negated flags.
+fn split_negations(command: &clap::Command, cli: Vec<OsString>) -> (Vec<OsString>, Vec<String>) {
+    let is_flag = |long: &str| {
+        command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(long) && !arg.is_takes_value_set())
+    };
+    let mut args = vec![];
+    let mut negated = vec![];
+    let mut cli = cli.into_iter();
+    for arg in cli.by_ref() {
+        if arg == "--" {
+            args.push(arg);
+            break;
+        }
+        match arg.to_str().and_then(|arg| arg.strip_prefix("--no-")) {
+            // Options like `--no-config` are not negations
+            Some(long) if is_flag(long) && !is_flag(&format!("no-{long}")) => {
+                negated.push(long.to_owned())
+            }
+            _ => args.push(arg),
+        }
+    }
+    args.extend(cli);
+    (args, negated)
+}
+
+/// Whether the option with the long name `long` was given on the command line, before or
+/// after a subcommand.
+fn given_on_command_line(command: &clap::Command, matches: &ArgMatches, long: &str) -> bool {
+    let id = match command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+    {
+        Some(arg) => arg.get_id(),
+        None => return false,
+    };
+    let given = |matches: &ArgMatches| matches.value_source(id) == Some(ValueSource::CommandLine);
+    given(matches) || matches.subcommand().is_some_and(|(_, sub)| given(sub))
+}
+
+/// `$XDG_CONFIG_HOME/lc/lc.toml`, falling back to `~/.config/lc/lc.toml`, if it exists.
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    let path = config_home.join("lc").join(CONFIG_FILE);
+    path.is_file().then_some(path)
+}
+
+/// The nearest `lc.toml` in the directory of `target` or any of its parents.
+fn project_config_path(target: &str) -> Option<PathBuf> {
+    let target = std::fs::canonicalize(target).ok()?;
+    target
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+fn read_config(path: PathBuf) -> Result<Config> {
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            return Err(Error::LcConfigError {
+                path,
+                message: err.to_string(),
+            })
+        }
+    };
+    match contents.parse::<Table>() {
+        Ok(table) => Ok(Config { path, table }),
+        Err(err) => Err(Error::LcConfigError {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+/// Looks up the table of the profile `name` in a config file.
+fn profile<'a>(config: &'a Config, name: &str) -> Option<Result<&'a Table>> {
+    let value = config.table.get("profile")?.as_table()?.get(name)?;
+    Some(value.as_table().ok_or_else(|| Error::LcConfigError {
+        path: config.path.clone(),
+        message: format!("profile `{name}` is not a table"),
+    }))
+}
+
+/// Adds the options of a config table to `options` by their long names, replacing the values
+/// of earlier config files.
+fn add_options<'a>(
+    options: &mut BTreeMap<String, (&'a Config, &'a Value)>,
+    command: &clap::Command,
+    config: &'a Config,
+    table: &'a Table,
+) -> Result<()> {
+    for (key, value) in table {
+        if key == "profile" {
+            continue;
+        }
+        let long = key.replace('_', "-");
+        if matches!(long.as_str(), "profile" | "no-config" | "help" | "version")
+            || !command.get_arguments().any(|a| a.get_long() == Some(&long))
+        {
+            return Err(Error::LcConfigError {
+                path: config.path.clone(),
+                message: format!("unknown option `{key}`"),
+            });
+        }
+        options.insert(long, (config, value));
+    }
+    Ok(())
+}
+
+/// Turns an option of a config file into the equivalent command line arguments.
+fn option_to_args(config: &Config, long: &str, value: &Value) -> Result<Vec<OsString>> {
+    let values = match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+    let mut args = vec![];
+    for value in values {
+        match value {
+            Value::Boolean(true) => args.push(format!("--{long}")),
+            Value::Boolean(false) => {}
+            Value::String(s) => args.push(format!("--{long}={s}")),
+            Value::Integer(i) => args.push(format!("--{long}={i}")),
+            Value::Float(f) => args.push(format!("--{long}={f}")),
+            _ => {
+                return Err(Error::LcConfigError {
+                    path: config.path.clone(),
+                    message: format!("unsupported value for `{long}`"),
+                })
+            }
+        }
+    }
+    Ok(args.into_iter().map(OsString::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(cli: &[&str], config: &str) -> Args {
+        let cli = std::iter::once("lc").chain(cli.iter().copied());
+        parse_args_from(cli.map(OsString::from).collect(), |_| {
+            Ok(vec![Config {
+                path: PathBuf::from(CONFIG_FILE),
+                table: config.parse().unwrap(),
+            }])
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn config_options_are_applied() {
+        let args = parse(&["."], "recursive = true\next = [\"rs\", \"toml\"]");
+        assert!(args.recursive);
+        assert_eq!(args.ext, ["rs", "toml"]);
+    }
+
+    #[test]
+    fn command_line_list_replaces_config_list() {
+        let args = parse(&["--ext", "txt", "."], "ext = [\"rs\", \"toml\"]");
+        assert_eq!(args.ext, ["txt"]);
+    }
+
+    #[test]
+    fn negation_turns_off_config_flag() {
+        let args = parse(&["--no-recursive", "."], "recursive = true");
+        assert!(!args.recursive);
+    }
+
+    #[test]
+    fn no_config_is_not_a_negation() {
+        let args = parse(&["--no-config", "."], "recursive = true");
+        assert!(args.no_config);
+        assert!(!args.recursive);
+    }
+
+    #[test]
+    fn global_option_after_subcommand_replaces_config() {
+        let args = parse(&["diff", "v1", "v2", "--ext", "txt"], "ext = [\"rs\"]");
+        assert_eq!(args.ext, ["txt"]);
+        assert!(matches!(args.command, Some(crate::Command::Diff { .. })));
+    }
+}
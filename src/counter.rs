@@ -0,0 +1,326 @@
+//! Streaming counting of file contents.
+//!
+//! Files are never loaded as a whole: they are read in fixed-size buffers (or memory
+//! mapped) and fed to a [`ContentCounter`] chunk by chunk.
+
+use crate::language::{Classifier, Language, LineKind};
+use crate::words::{WordCounter, WordMode};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Size of the buffer files are read in.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Files of at least this size are memory mapped when memory mapping is enabled.
+pub const MMAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Files containing more than this share of control characters in their first block are binary.
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+/// Only this many bytes of a line are kept for telling code and comments apart,
+/// so that files without line breaks can still be counted in constant memory.
+const MAX_CLASSIFIED_LINE: usize = 64 * 1024;
+
+/// The raw results of counting the contents of a file.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Counts {
+    pub bytes: usize,
+    pub lines: usize,
+    /// Line feed characters, which unlike `lines` leaves out a last line without one
+    pub newlines: usize,
+    /// Lines containing nothing but whitespace
+    pub empty_lines: usize,
+    /// Unicode scalar values
+    pub characters: usize,
+    /// Extended grapheme clusters
+    pub graphemes: usize,
+    /// Characters that are neither whitespace nor control characters
+    pub visible: usize,
+    /// Invalid UTF-8 sequences, each counted as one replacement character
+    pub invalid: usize,
+    pub words: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+    /// Whether the contents look like binary data
+    pub binary: bool,
+}
+
+/// Counts contents that are fed to it in chunks of any size.
+///
+/// Invalid UTF-8 is counted as one replacement character per invalid sequence.
+pub struct ContentCounter {
+    classifier: Classifier,
+    words: WordCounter,
+    graphemes: GraphemeCounter,
+    /// Start of a UTF-8 sequence that was cut off at the end of the previous chunk
+    pending: Vec<u8>,
+    /// The start of the current line, see [`MAX_CLASSIFIED_LINE`]
+    line: String,
+    /// Whether the current line contains any characters yet
+    line_started: bool,
+    /// Whether the current line contains only whitespace so far
+    line_blank: bool,
+    counts: Counts,
+}
+
+impl ContentCounter {
+    pub fn new(language: Option<&'static Language>, word_mode: WordMode) -> Self {
+        Self {
+            classifier: Classifier::new(language),
+            words: WordCounter::new(word_mode),
+            graphemes: GraphemeCounter::default(),
+            pending: Vec::new(),
+            line: String::new(),
+            line_started: false,
+            line_blank: true,
+            counts: Counts::default(),
+        }
+    }
+
+    /// Counts the next chunk of the contents.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.counts.bytes += chunk.len();
+        self.feed(chunk);
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        if chunk.is_empty() {
+            return;
+        }
+        if self.pending.is_empty() {
+            self.decode(chunk);
+            return;
+        }
+        // Complete the cut off sequence first, a UTF-8 sequence is at most 4 bytes long
+        let take = (4 - self.pending.len()).min(chunk.len());
+        let mut start = std::mem::take(&mut self.pending);
+        start.extend_from_slice(&chunk[..take]);
+        self.decode(&start);
+        self.feed(&chunk[take..]);
+    }
+
+    /// Counts the last line and returns the results.
+    pub fn finish(mut self) -> Counts {
+        if !self.pending.is_empty() {
+            self.count_invalid();
+        }
+        if self.line_started {
+            self.end_line();
+        }
+        Counts {
+            words: self.words.finish(),
+            graphemes: self.graphemes.finish(),
+            ..self.counts
+        }
+    }
+
+    fn decode(&mut self, mut bytes: &[u8]) {
+        loop {
+            match std::str::from_utf8(bytes) {
+                Ok(s) => return self.count_str(s),
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    self.count_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match err.error_len() {
+                        Some(len) => {
+                            self.count_invalid();
+                            bytes = &rest[len..];
+                        }
+                        None => {
+                            self.pending.extend_from_slice(rest);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn count_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.words.push(c);
+            self.count_char(c);
+        }
+    }
+
+    /// Counts an invalid sequence as a replacement character. Like in `wc` it neither
+    /// starts nor ends a word.
+    fn count_invalid(&mut self) {
+        self.counts.invalid += 1;
+        self.count_char(char::REPLACEMENT_CHARACTER);
+    }
+
+    fn count_char(&mut self, c: char) {
+        self.counts.characters += 1;
+        if !c.is_whitespace() && !c.is_control() {
+            self.counts.visible += 1;
+        }
+        self.graphemes.push(c);
+        if c == '\n' {
+            self.counts.newlines += 1;
+            self.end_line();
+            return;
+        }
+        self.line_started = true;
+        self.line_blank &= c.is_whitespace();
+        if self.line.len() < MAX_CLASSIFIED_LINE {
+            self.line.push(c);
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.counts.lines += 1;
+        if self.line_blank {
+            self.counts.empty_lines += 1;
+        }
+        match self.classifier.classify(&self.line) {
+            LineKind::Code => self.counts.code += 1,
+            LineKind::Comment => self.counts.comments += 1,
+            // Only the start of a long line may have been classified
+            LineKind::Blank if !self.line_blank => self.counts.code += 1,
+            LineKind::Blank => self.counts.blanks += 1,
+        }
+        self.line.clear();
+        self.line_started = false;
+        self.line_blank = true;
+    }
+}
+
+/// Counts the extended grapheme clusters of text that is fed to it character by character.
+#[derive(Default)]
+struct GraphemeCounter {
+    /// The characters since the last control character
+    run: String,
+    /// Whether the last character was a carriage return, which forms one cluster with a line feed
+    after_cr: bool,
+    graphemes: usize,
+}
+
+impl GraphemeCounter {
+    fn push(&mut self, c: char) {
+        // Clusters always end before and after control characters, so each run between
+        // them can be segmented on its own
+        if c.is_control() {
+            self.end_run();
+            if !(c == '\n' && self.after_cr) {
+                self.graphemes += 1;
+            }
+            self.after_cr = c == '\r';
+            return;
+        }
+        self.after_cr = false;
+        self.run.push(c);
+        if self.run.len() >= MAX_CLASSIFIED_LINE {
+            self.end_run();
+        }
+    }
+
+    fn finish(mut self) -> usize {
+        self.end_run();
+        self.graphemes
+    }
+
+    fn end_run(&mut self) {
+        if !self.run.is_empty() {
+            self.graphemes += self.run.graphemes(true).count();
+            self.run.clear();
+        }
+    }
+}
+
+/// Checks whether a block of data looks like binary data rather than text: it either contains
+/// a NUL byte, or too many control characters that do not appear in text files.
+pub fn is_binary(block: &[u8]) -> bool {
+    if block.contains(&0) {
+        return true;
+    }
+    let control = block
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    control as f64 > block.len() as f64 * MAX_CONTROL_RATIO
+}
+
+/// Counts a file, memory mapping it instead of reading it in chunks if `mmap` is set
+/// and the file is at least [`MMAP_THRESHOLD`] bytes large.
+///
+/// Binary files are detected by their first block and skipped, returning `None`,
+/// unless `include_binary` is set.
+pub fn count_file(
+    path: impl AsRef<Path>,
+    language: Option<&'static Language>,
+    word_mode: WordMode,
+    mmap: bool,
+    include_binary: bool,
+) -> io::Result<Option<Counts>> {
+    let file = File::open(path)?;
+    if mmap && file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: the map is only read while counting. If another process modifies the file
+        // meanwhile the counts may be off, but if it truncates the file, e.g. when rotating
+        // a log, reading the pages past the new end raises SIGBUS and the process is killed.
+        // This is documented for `--mmap`, which is off by default.
+        let map = unsafe { Mmap::map(&file)? };
+        return Ok(count_bytes(&map, language, word_mode, include_binary));
+    }
+    count_reader(file, language, word_mode, include_binary)
+}
+
+/// Counts everything read from `reader` in chunks of [`BUFFER_SIZE`].
+///
+/// Returns `None` if the first chunk looks binary, unless `include_binary` is set.
+pub fn count_reader(
+    mut reader: impl Read,
+    language: Option<&'static Language>,
+    word_mode: WordMode,
+    include_binary: bool,
+) -> io::Result<Option<Counts>> {
+    let mut counter = ContentCounter::new(language, word_mode);
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut first_block = true;
+    let mut binary = false;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if first_block {
+            first_block = false;
+            binary = is_binary(&buffer[..n]);
+            if binary && !include_binary {
+                return Ok(None);
+            }
+        }
+        counter.update(&buffer[..n]);
+    }
+    Ok(Some(Counts {
+        binary,
+        ..counter.finish()
+    }))
+}
+
+/// Counts contents that are already in memory.
+///
+/// Returns `None` if they look binary, unless `include_binary` is set.
+pub fn count_bytes(
+    bytes: &[u8],
+    language: Option<&'static Language>,
+    word_mode: WordMode,
+    include_binary: bool,
+) -> Option<Counts> {
+    let binary = is_binary(&bytes[..BUFFER_SIZE.min(bytes.len())]);
+    if binary && !include_binary {
+        return None;
+    }
+    let mut counter = ContentCounter::new(language, word_mode);
+    counter.update(bytes);
+    Some(Counts {
+        binary,
+        ..counter.finish()
+    })
+}
@@ -0,0 +1,184 @@
+//! Flat CSV and TSV output with one row per file.
+
+use lc::diff::FileDiff;
+use lc::history::Snapshot;
+use lc::FileData;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::Path;
+
+const HEADER: &[&str] = &[
+    "path",
+    "directory",
+    "extension",
+    "language",
+    "lines",
+    "characters",
+    "words",
+    "code",
+    "comments",
+    "blanks",
+    "binary",
+    "bytes",
+    "graphemes",
+    "visible",
+];
+
+/// Writes a header row followed by one row per file, separated by `delimiter`.
+pub fn write_files<'a>(
+    mut out: impl Write,
+    files: impl IntoIterator<Item = &'a FileData>,
+    delimiter: char,
+) -> io::Result<()> {
+    write_row(&mut out, HEADER.iter().copied(), delimiter)?;
+    for file in files {
+        let path = Path::new(&file.file_name);
+        let directory = path
+            .parent()
+            .map(|p| p.to_string_lossy())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy())
+            .unwrap_or_default();
+        let counts = [
+            file.lines,
+            file.characters,
+            file.words,
+            file.code,
+            file.comments,
+            file.blanks,
+        ]
+        .map(|count| count.to_string());
+        // Added after the first release, so they come last
+        let characters = [file.bytes, file.graphemes, file.visible].map(|count| count.to_string());
+
+        let fields = [
+            file.file_name.as_str(),
+            &directory,
+            &extension,
+            file.language.map_or("", |lang| lang.name),
+        ];
+        let binary = if file.binary { "true" } else { "false" };
+        write_row(
+            &mut out,
+            fields
+                .into_iter()
+                .chain(counts.iter().map(String::as_str))
+                .chain([binary])
+                .chain(characters.iter().map(String::as_str)),
+            delimiter,
+        )?;
+    }
+    Ok(())
+}
+
+const DIFF_HEADER: &[&str] = &[
+    "path", "language", "before", "after", "added", "removed", "net",
+];
+
+/// Writes a header row followed by one row per file of `lc diff`, separated by `delimiter`.
+pub fn write_file_diffs<'a>(
+    mut out: impl Write,
+    files: impl IntoIterator<Item = &'a FileDiff>,
+    delimiter: char,
+) -> io::Result<()> {
+    write_row(&mut out, DIFF_HEADER.iter().copied(), delimiter)?;
+    for file in files {
+        let lines = file.lines;
+        let counts =
+            [lines.before, lines.after, lines.added, lines.removed].map(|count| count.to_string());
+        let net = lines.net().to_string();
+        let fields = [
+            file.file_name.as_str(),
+            file.language.map_or("", |lang| lang.name),
+        ];
+        write_row(
+            &mut out,
+            fields
+                .into_iter()
+                .chain(counts.iter().map(String::as_str))
+                .chain([net.as_str()]),
+            delimiter,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a header row followed by one row per commit of `lc history`, with the lines of
+/// every language that appears in any of the commits in a column of its own.
+pub fn write_history(
+    mut out: impl Write,
+    snapshots: &[Snapshot],
+    delimiter: char,
+) -> io::Result<()> {
+    let languages: BTreeSet<&str> = snapshots
+        .iter()
+        .flat_map(|snapshot| &snapshot.languages)
+        .map(|lang| lang.name)
+        .collect();
+    let header = [
+        "commit", "date", "tags", "files", "lines", "code", "comments", "blanks",
+    ];
+    write_row(
+        &mut out,
+        header.into_iter().chain(languages.iter().copied()),
+        delimiter,
+    )?;
+    for snapshot in snapshots {
+        let totals = &snapshot.totals;
+        let counts = [
+            totals.files,
+            totals.lines,
+            totals.code,
+            totals.comments,
+            totals.blanks,
+        ]
+        .map(|count| count.to_string());
+        let language_lines: Vec<String> = languages
+            .iter()
+            .map(|name| {
+                let lang = snapshot.languages.iter().find(|lang| lang.name == *name);
+                lang.map_or(0, |lang| lang.lines).to_string()
+            })
+            .collect();
+        let fields = [
+            snapshot.commit.clone(),
+            snapshot.date(),
+            snapshot.tags.join(" "),
+        ];
+        write_row(
+            &mut out,
+            fields
+                .iter()
+                .chain(&counts)
+                .chain(&language_lines)
+                .map(String::as_str),
+            delimiter,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_row<'a>(
+    out: &mut impl Write,
+    fields: impl IntoIterator<Item = &'a str>,
+    delimiter: char,
+) -> io::Result<()> {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            write!(out, "{delimiter}")?;
+        }
+        write!(out, "{}", quote(field, delimiter))?;
+    }
+    writeln!(out)
+}
+
+/// Quotes a field if it contains the delimiter, quotes or line breaks, doubling any quotes within.
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,189 @@
+//! Line count deltas between two counts of the same tree, e.g. at two git revisions.
+
+use crate::language::Language;
+use crate::{DirData, FileData, FileError};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+
+/// The lines of a file, directory or language before and after a change.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineDelta {
+    pub before: usize,
+    pub after: usize,
+    /// Lines that were added, including the new versions of changed lines
+    pub added: usize,
+    /// Lines that were removed, including the old versions of changed lines
+    pub removed: usize,
+}
+
+impl LineDelta {
+    /// The change of the line count, `after - before`.
+    pub fn net(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.added > 0 || self.removed > 0 || self.before != self.after
+    }
+}
+
+impl AddAssign for LineDelta {
+    fn add_assign(&mut self, other: Self) {
+        self.before += other.before;
+        self.after += other.after;
+        self.added += other.added;
+        self.removed += other.removed;
+    }
+}
+
+/// The delta of a single file, which may only exist on one side.
+#[derive(Debug)]
+pub struct FileDiff {
+    pub file_name: String,
+    pub language: Option<&'static Language>,
+    pub lines: LineDelta,
+}
+
+impl FileDiff {
+    pub fn language_name(&self) -> &'static str {
+        self.language.map_or("Other", |lang| lang.name)
+    }
+}
+
+/// The deltas of a directory, sorted by name. Unchanged files are included as well,
+/// so that the totals cover the whole directory.
+#[derive(Debug)]
+pub struct DirDiff {
+    pub dir_name: String,
+    pub files: Vec<FileDiff>,
+    pub sub_dirs: Vec<DirDiff>,
+    /// Files and directories that could not be counted on either side
+    pub errors: Vec<FileError>,
+}
+
+impl DirDiff {
+    /// Compares two counts of the directory. `changes` holds the lines added and removed per
+    /// file name for the files whose contents differ; files only on one side that are missing
+    /// from it count as added or removed as a whole.
+    pub(crate) fn new(
+        old: DirData,
+        new: DirData,
+        changes: &HashMap<String, (usize, usize)>,
+    ) -> Self {
+        let mut files: BTreeMap<String, (Option<FileData>, Option<FileData>)> = BTreeMap::new();
+        for file in old.file_data {
+            let name = file.file_name.clone();
+            files.entry(name).or_default().0 = Some(file);
+        }
+        for file in new.file_data {
+            let name = file.file_name.clone();
+            files.entry(name).or_default().1 = Some(file);
+        }
+        let files = files
+            .into_iter()
+            .map(|(file_name, (old, new))| {
+                let before = old.as_ref().map_or(0, |file| file.lines);
+                let after = new.as_ref().map_or(0, |file| file.lines);
+                let (added, removed) = match (changes.get(&file_name), &old, &new) {
+                    (Some(&change), _, _) => change,
+                    (None, Some(_), Some(_)) => (0, 0),
+                    (None, _, _) => (after, before),
+                };
+                FileDiff {
+                    language: new.or(old).and_then(|file| file.language),
+                    file_name,
+                    lines: LineDelta {
+                        before,
+                        after,
+                        added,
+                        removed,
+                    },
+                }
+            })
+            .collect();
+
+        let mut dirs: BTreeMap<String, (Option<DirData>, Option<DirData>)> = BTreeMap::new();
+        for dir in old.sub_dirs {
+            let name = dir.dir_name.clone();
+            dirs.entry(name).or_default().0 = Some(dir);
+        }
+        for dir in new.sub_dirs {
+            let name = dir.dir_name.clone();
+            dirs.entry(name).or_default().1 = Some(dir);
+        }
+        let sub_dirs = dirs
+            .into_iter()
+            .map(|(name, (old, new))| {
+                let old = old.unwrap_or_else(|| DirData::new(&name));
+                let new = new.unwrap_or_else(|| DirData::new(&name));
+                DirDiff::new(old, new, changes)
+            })
+            .collect();
+
+        let mut errors = old.errors;
+        errors.extend(new.errors);
+        Self {
+            dir_name: new.dir_name,
+            files,
+            sub_dirs,
+            errors,
+        }
+    }
+
+    /// Delta of the files directly inside this directory.
+    pub fn direct_totals(&self) -> LineDelta {
+        let mut totals = LineDelta::default();
+        for file in &self.files {
+            totals += file.lines;
+        }
+        totals
+    }
+
+    /// Delta of the files in this directory and all of its subdirectories.
+    pub fn recursive_totals(&self) -> LineDelta {
+        let mut totals = self.direct_totals();
+        for dir in &self.sub_dirs {
+            totals += dir.recursive_totals();
+        }
+        totals
+    }
+
+    /// Every file in this directory and its subdirectories.
+    pub fn all_files(&self) -> Vec<&FileDiff> {
+        let mut files: Vec<&FileDiff> = self.files.iter().collect();
+        for dir in &self.sub_dirs {
+            files.extend(dir.all_files());
+        }
+        files
+    }
+
+    /// Every error in this directory and its subdirectories.
+    pub fn all_errors(&self) -> Vec<&FileError> {
+        let mut errors: Vec<&FileError> = self.errors.iter().collect();
+        for dir in &self.sub_dirs {
+            errors.extend(dir.all_errors());
+        }
+        errors
+    }
+}
+
+/// The delta of all files of a language.
+#[derive(Debug, Default)]
+pub struct LanguageDiff {
+    pub name: &'static str,
+    pub lines: LineDelta,
+}
+
+/// Groups the deltas of the given files by language, sorted by language name.
+pub fn summarize_languages<'a>(files: impl IntoIterator<Item = &'a FileDiff>) -> Vec<LanguageDiff> {
+    let mut summary: BTreeMap<&'static str, LanguageDiff> = BTreeMap::new();
+    for file in files {
+        let name = file.language_name();
+        let entry = summary.entry(name).or_insert_with(|| LanguageDiff {
+            name,
+            ..Default::default()
+        });
+        entry.lines += file.lines;
+    }
+    summary.into_values().collect()
+}
@@ -0,0 +1,140 @@
+//! Filtering of the files to count by glob, extension and size.
+
+use globset::{Glob, GlobMatcher};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Selects the files to count when walking directories.
+///
+/// Globs are matched against paths relative to the counted directory, so `tests/**`
+/// matches everything below its `tests` directory. `*` also matches `/`, so `*.rs`
+/// matches Rust files at any depth.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    include: Vec<GlobMatcher>,
+    exclude: Vec<GlobMatcher>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Filters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only counts files matching `glob`, or any of the other include globs.
+    pub fn include(mut self, glob: &str) -> Result<Self, globset::Error> {
+        self.include.push(Glob::new(glob)?.compile_matcher());
+        Ok(self)
+    }
+
+    /// Skips files and directories matching `glob`.
+    pub fn exclude(mut self, glob: &str) -> Result<Self, globset::Error> {
+        self.exclude.push(Glob::new(glob)?.compile_matcher());
+        Ok(self)
+    }
+
+    /// Only counts files with one of the given extensions, compared case-insensitively.
+    pub fn extensions<S: AsRef<str>>(mut self, extensions: impl IntoIterator<Item = S>) -> Self {
+        self.extensions = extensions
+            .into_iter()
+            .map(|ext| ext.as_ref().trim_start_matches('.').to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Skips files smaller than `size` bytes.
+    pub fn min_size(mut self, size: Option<u64>) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Skips files larger than `size` bytes.
+    pub fn max_size(mut self, size: Option<u64>) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Checks whether a directory is excluded, `path` being relative to the counted directory.
+    pub(crate) fn check_dir(&self, path: &Path) -> Option<Exclusion> {
+        self.excluded_by(path)
+    }
+
+    /// Checks whether a file is excluded, `path` being relative to the counted directory.
+    pub(crate) fn check_file(&self, path: &Path, size: u64) -> Option<Exclusion> {
+        if let Some(exclusion) = self.excluded_by(path) {
+            return Some(exclusion);
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|glob| glob.is_match(path)) {
+            return Some(Exclusion::NotIncluded);
+        }
+        if !self.extensions.is_empty() {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+            if !extension.is_some_and(|ext| self.extensions.contains(&ext)) {
+                return Some(Exclusion::Extension);
+            }
+        }
+        match (self.min_size, self.max_size) {
+            (Some(min), _) if size < min => Some(Exclusion::TooSmall(min)),
+            (_, Some(max)) if size > max => Some(Exclusion::TooLarge(max)),
+            _ => None,
+        }
+    }
+
+    fn excluded_by(&self, path: &Path) -> Option<Exclusion> {
+        self.exclude
+            .iter()
+            .find(|glob| glob.is_match(path))
+            .map(|glob| Exclusion::Excluded(glob.glob().glob().to_owned()))
+    }
+}
+
+/// Why a file or directory was not counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exclusion {
+    /// The name starts with a `.`
+    Hidden,
+    /// Matched by a pattern of an ignore file
+    Ignored {
+        pattern: String,
+        /// The ignore file the pattern was read from
+        file: Option<PathBuf>,
+    },
+    /// Matched by none of the include globs
+    NotIncluded,
+    /// Matched by the given exclude glob
+    Excluded(String),
+    /// Has none of the selected extensions
+    Extension,
+    /// Smaller than the given minimum size
+    TooSmall(u64),
+    /// Larger than the given maximum size
+    TooLarge(u64),
+    /// A directory on another file system than the counted directory
+    OtherFileSystem,
+}
+
+impl fmt::Display for Exclusion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exclusion::Ignored {
+                pattern,
+                file: Some(file),
+            } => write!(f, "ignored by `{pattern}` in {}", file.display()),
+            Exclusion::Ignored {
+                pattern,
+                file: None,
+            } => write!(f, "ignored by `{pattern}`"),
+            Exclusion::Hidden => write!(f, "hidden"),
+            Exclusion::NotIncluded => write!(f, "not matched by any include pattern"),
+            Exclusion::Excluded(glob) => write!(f, "excluded by `{glob}`"),
+            Exclusion::Extension => write!(f, "extension not selected"),
+            Exclusion::TooSmall(min) => write!(f, "smaller than {min} bytes"),
+            Exclusion::TooLarge(max) => write!(f, "larger than {max} bytes"),
+            Exclusion::OtherFileSystem => write!(f, "on another file system"),
+        }
+    }
+}
@@ -0,0 +1,805 @@
+//! Counting and comparing the trees of commits, read from the object database of a git
+//! repository without touching the working tree, the blame of working tree files and the
+//! selection of tracked, staged or changed files.
+
+use crate::counter::Counts;
+use crate::diff::DirDiff;
+use crate::filter::Exclusion;
+use crate::history::{Sample, Snapshot};
+use crate::ignores::{Ignores, IGNORE_FILES};
+use crate::language::Language;
+use crate::{
+    counter, summarize_languages, AuthorLines, Counted, Counter, DirData, Error, ExcludedFile,
+    FileError, PathData, Result, SkippedFile, Totals, NOT_COMMITTED,
+};
+use git2::{BlameOptions, Blob, DiffOptions, ErrorCode, ObjectType, Oid, Patch, Repository, Tree};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Mutex, PoisonError};
+
+/// File mode of symbolic links in trees.
+const MODE_LINK: i32 = 0o120000;
+
+/// File mode of directories in trees.
+const MODE_DIR: i32 = 0o040000;
+
+/// File mode of submodules in trees and the index.
+const MODE_SUBMODULE: i32 = 0o160000;
+
+/// The bits of the flags of an index entry holding its merge stage, 0 unless conflicted.
+const INDEX_STAGE_MASK: u16 = 0x3000;
+
+thread_local! {
+    /// The repository opened by this thread, as repositories cannot be shared between threads.
+    static REPO: RefCell<Option<Rc<Repository>>> = const { RefCell::new(None) };
+}
+
+/// An entry of a tree, copied out of it so that it can be counted on any thread.
+#[derive(Clone)]
+struct Entry {
+    name: String,
+    id: Oid,
+    kind: Option<ObjectType>,
+    mode: i32,
+    /// The entries of a directory of the index, which has no tree object
+    children: Option<Vec<Entry>>,
+}
+
+/// The entries of a directory to walk.
+enum Dir<'a> {
+    Tree(Oid),
+    Index(&'a [Entry]),
+}
+
+/// Which files of a git repository are counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GitFiles {
+    /// Every file in the working tree, without looking at the repository
+    #[default]
+    All,
+    /// Only files in the index
+    Tracked,
+    /// Only files whose staged contents differ from `HEAD`, counted as they are staged
+    Staged,
+    /// Only files in the working tree that differ from the given commit, like in `git diff <REF>`
+    ChangedSince(String),
+}
+
+/// The files a walk is restricted to, relative to the counted directory.
+#[derive(Debug, Default)]
+pub(crate) struct Selection {
+    files: HashSet<PathBuf>,
+    /// Every directory containing one of the files
+    dirs: HashSet<PathBuf>,
+}
+
+impl Selection {
+    fn insert(&mut self, file: &Path) {
+        for dir in file.ancestors().skip(1) {
+            if !self.dirs.insert(dir.to_owned()) {
+                break;
+            }
+        }
+        self.files.insert(file.to_owned());
+    }
+
+    pub(crate) fn contains_file(&self, relative: &Path) -> bool {
+        self.files.contains(relative)
+    }
+
+    pub(crate) fn contains_dir(&self, relative: &Path) -> bool {
+        self.dirs.contains(relative)
+    }
+}
+
+/// A blob id and the name of the language it is counted as.
+type BlobKey = (Oid, Option<&'static str>);
+
+/// Counts of blobs, so that files which did not change between commits are only counted once.
+/// `None` for skipped binary blobs.
+#[derive(Default)]
+struct BlobCache(Mutex<HashMap<BlobKey, Option<Counts>>>);
+
+/// The state shared by the whole walk of a tree, like [`crate::Walk`] for directories.
+struct TreeWalk<'a> {
+    git_dir: &'a Path,
+    /// The counted path, which the filters are relative to
+    root: &'a Path,
+    counter: &'a Counter,
+    cache: &'a BlobCache,
+    /// The files the walk is restricted to, if any
+    selection: Option<&'a Selection>,
+}
+
+/// Counts `path` as it is in the commit `rev` of the repository containing it.
+pub(crate) fn count_rev(counter: &Counter, rev: &str, path: &str) -> Result<PathData> {
+    counter.thread_pool()?.install(|| {
+        let (repo, in_repo) = open(path)?;
+        let tree = commit_tree(&repo, rev)?;
+        let cache = BlobCache::default();
+        match count_tree(&repo, &tree, &in_repo, path, counter, &cache, None)? {
+            Some(data) => Ok(data),
+            None => Err(git2::Error::from_str(&format!("{path} does not exist in {rev}")).into()),
+        }
+    })
+}
+
+/// Counts the files below `path` whose staged contents differ from `HEAD`, reading the
+/// contents from the index of the repository containing `path`.
+pub(crate) fn count_staged(counter: &Counter, path: &str) -> Result<PathData> {
+    counter.thread_pool()?.install(|| {
+        let (repo, in_repo) = open(path)?;
+        let selection = selection(&repo, &in_repo, &GitFiles::Staged)?;
+        let mut entry = &index_entries(&repo)?;
+        for name in in_repo.iter() {
+            let child = entry.children.iter().flatten().find(|e| *e.name == *name);
+            entry = match child {
+                Some(child) => child,
+                None => {
+                    let message = format!("{path} does not exist in the index");
+                    return Err(git2::Error::from_str(&message).into());
+                }
+            };
+        }
+
+        match &entry.children {
+            Some(children) => {
+                let cache = BlobCache::default();
+                let walk = TreeWalk {
+                    git_dir: repo.path(),
+                    root: Path::new(path),
+                    counter,
+                    cache: &cache,
+                    selection: Some(&selection),
+                };
+                let dir = get_tree_data(&walk, Dir::Index(children), path, &Ignores::default())?;
+                Ok(PathData::Dir(dir))
+            }
+            None => count_file_blob(&repo.find_blob(entry.id)?, path, counter),
+        }
+    })
+}
+
+/// The files below `path` in the working tree selected by `files`.
+pub(crate) fn select(path: &str, files: &GitFiles) -> Result<Selection> {
+    let (repo, in_repo) = open(path)?;
+    selection(&repo, &in_repo, files)
+}
+
+fn selection(repo: &Repository, in_repo: &Path, files: &GitFiles) -> Result<Selection> {
+    let mut selection = Selection::default();
+    let mut insert = |file: &Path| {
+        if let Ok(relative) = file.strip_prefix(in_repo) {
+            selection.insert(relative);
+        }
+    };
+
+    let mut options = DiffOptions::new();
+    if !in_repo.as_os_str().is_empty() {
+        options.pathspec(in_repo);
+    }
+    let diff = match files {
+        GitFiles::All => unreachable!("every file is counted without a selection"),
+        GitFiles::Tracked => {
+            for entry in repo.index()?.iter() {
+                match std::str::from_utf8(&entry.path) {
+                    Ok(file) => insert(Path::new(file)),
+                    Err(_) => {
+                        let file = String::from_utf8_lossy(&entry.path).into_owned();
+                        return Err(Error::LcInvalidPathError(file.into()));
+                    }
+                }
+            }
+            return Ok(selection);
+        }
+        GitFiles::Staged => {
+            let head = match repo.head() {
+                Ok(head) => Some(head.peel_to_tree()?),
+                Err(err) if matches!(err.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound) => {
+                    None
+                }
+                Err(err) => return Err(err.into()),
+            };
+            repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))?
+        }
+        GitFiles::ChangedSince(rev) => {
+            let tree = commit_tree(repo, rev)?;
+            repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?
+        }
+    };
+    // Deleted files are selected as well, but do not exist to be counted
+    for delta in diff.deltas() {
+        if let Some(file) = delta.new_file().path() {
+            insert(file);
+        }
+    }
+    Ok(selection)
+}
+
+/// Counts `path` in the commits `old` and `new` of the repository containing it and compares
+/// the counts, with the lines added and removed per file taken from the diff of the commits.
+pub(crate) fn diff_revs(counter: &Counter, old: &str, new: &str, path: &str) -> Result<DirDiff> {
+    counter.thread_pool()?.install(|| {
+        let (repo, in_repo) = open(path)?;
+        let old_tree = commit_tree(&repo, old)?;
+        let new_tree = commit_tree(&repo, new)?;
+        let cache = BlobCache::default();
+        let old_data = count_tree(&repo, &old_tree, &in_repo, path, counter, &cache, None)?;
+        let new_data = count_tree(&repo, &new_tree, &in_repo, path, counter, &cache, None)?;
+        let changes = line_changes(&repo, &old_tree, &new_tree, &in_repo, path)?;
+
+        // A file is compared as the only file of its parent directory
+        let name = match (&old_data, &new_data) {
+            (None, None) => {
+                let message = format!("{path} exists in neither {old} nor {new}");
+                return Err(git2::Error::from_str(&message).into());
+            }
+            (Some(PathData::File(_)), _) | (_, Some(PathData::File(_))) => {
+                match Path::new(path).parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+                    _ => ".".into(),
+                }
+            }
+            _ => path.into(),
+        };
+        let into_dir = |data| match data {
+            Some(PathData::Dir(dir)) => dir,
+            Some(PathData::File(file)) => {
+                let mut dir = DirData::new(name.as_ref());
+                dir.push(Counted::File(file));
+                dir
+            }
+            None => DirData::new(name.as_ref()),
+        };
+        Ok(DirDiff::new(
+            into_dir(old_data),
+            into_dir(new_data),
+            &changes,
+        ))
+    })
+}
+
+/// Counts `path` in the first-parent history of `rev`, oldest commit first.
+pub(crate) fn history(
+    counter: &Counter,
+    rev: &str,
+    path: &str,
+    sample: Sample,
+) -> Result<Vec<Snapshot>> {
+    counter.thread_pool()?.install(|| {
+        let (repo, in_repo) = open(path)?;
+        let tags = tags(&repo)?;
+
+        let mut walk = repo.revwalk()?;
+        walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?;
+        walk.simplify_first_parent()?;
+        // Walking from the newest commit, the first one of every period is its last one
+        let mut periods = HashSet::new();
+        let mut commits = vec![];
+        for id in walk {
+            let id = id?;
+            let time = repo.find_commit(id)?.time().seconds();
+            let sampled = match sample.period(time) {
+                Some(period) => periods.insert(period),
+                None => sample != Sample::Tag || tags.contains_key(&id),
+            };
+            if sampled {
+                commits.push((id, time));
+            }
+        }
+
+        let cache = BlobCache::default();
+        commits
+            .into_iter()
+            .rev()
+            .map(|(id, time)| {
+                let tree = repo.find_commit(id)?.tree()?;
+                let data = count_tree(&repo, &tree, &in_repo, path, counter, &cache, None)?;
+                let (totals, languages) = match &data {
+                    Some(data) => (
+                        data.recursive_totals(),
+                        summarize_languages(data.all_files()),
+                    ),
+                    None => (Totals::default(), vec![]),
+                };
+                let errors = match data {
+                    Some(PathData::Dir(dir)) => dir.into_errors(),
+                    _ => vec![],
+                };
+                Ok(Snapshot {
+                    commit: id.to_string(),
+                    time,
+                    tags: tags.get(&id).cloned().unwrap_or_default(),
+                    totals,
+                    languages,
+                    errors,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Attributes the lines of the working tree file at `path` to the authors who last changed
+/// them, according to the blame of `HEAD` with the mailmap of the repository applied.
+/// Lines that differ from `HEAD`, and all lines of untracked files or of files outside of
+/// any repository, are not committed yet.
+pub(crate) fn blame(path: &str, skip_empty_lines: bool) -> Result<Vec<AuthorLines>> {
+    let contents = std::fs::read(path)?;
+    let mut lines: Vec<&[u8]> = contents.split(|&byte| byte == b'\n').collect();
+    // The line feed ends the last line instead of starting another one
+    if contents.is_empty() || contents.ends_with(b"\n") {
+        lines.pop();
+    }
+
+    let repo = match open(path) {
+        Ok(repo) => Some(repo),
+        Err(Error::LcGitError(err)) if err.code() == ErrorCode::NotFound => None,
+        Err(err) => return Err(err),
+    };
+    let committed = match &repo {
+        Some((repo, in_repo)) => {
+            match repo.blame_file(in_repo, Some(BlameOptions::new().use_mailmap(true))) {
+                Ok(blame) => Some(blame),
+                Err(err) if matches!(err.code(), ErrorCode::NotFound | ErrorCode::UnbornBranch) => {
+                    None
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        None => None,
+    };
+    // Blames the lines changed in the working tree on no commit
+    let blame = match &committed {
+        Some(blame) => Some(blame.blame_buffer(&contents)?),
+        None => None,
+    };
+
+    let mut authors: HashMap<(String, String), usize> = HashMap::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        if skip_empty_lines && String::from_utf8_lossy(line).trim().is_empty() {
+            continue;
+        }
+        let author = blame
+            .as_ref()
+            .and_then(|blame| blame.get_line(i + 1))
+            .filter(|hunk| !hunk.final_commit_id().is_zero())
+            .and_then(|hunk| {
+                let signature = hunk.final_signature()?;
+                Some((
+                    String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+                    String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+                ))
+            })
+            .unwrap_or_else(|| (NOT_COMMITTED.to_owned(), String::new()));
+        *authors.entry(author).or_default() += 1;
+    }
+    Ok(authors
+        .into_iter()
+        .map(|((name, email), lines)| AuthorLines { name, email, lines })
+        .collect())
+}
+
+fn commit_tree<'r>(repo: &'r Repository, rev: &str) -> Result<Tree<'r>> {
+    Ok(repo.revparse_single(rev)?.peel_to_commit()?.tree()?)
+}
+
+/// The names of the tags of every tagged commit, sorted by name.
+fn tags(repo: &Repository) -> Result<HashMap<Oid, Vec<String>>> {
+    let mut tags: HashMap<Oid, Vec<String>> = HashMap::new();
+    for name in repo.tag_names(None)?.iter() {
+        let name = match name? {
+            Some(name) => name,
+            None => continue,
+        };
+        let reference = repo.find_reference(&format!("refs/tags/{name}"))?;
+        // Tags of other objects than commits are of no interest
+        if let Ok(commit) = reference.peel_to_commit() {
+            tags.entry(commit.id()).or_default().push(name.to_owned());
+        }
+    }
+    for names in tags.values_mut() {
+        names.sort();
+    }
+    Ok(tags)
+}
+
+/// Counts the object at `in_repo` in `tree` as `path`, returning `None` if there is none.
+///
+/// Must be called on the thread pool of the counter.
+fn count_tree(
+    repo: &Repository,
+    tree: &Tree,
+    in_repo: &Path,
+    path: &str,
+    counter: &Counter,
+    cache: &BlobCache,
+    selection: Option<&Selection>,
+) -> Result<Option<PathData>> {
+    let object = if in_repo.as_os_str().is_empty() {
+        tree.as_object().clone()
+    } else {
+        match tree.get_path(in_repo) {
+            Ok(entry) => entry.to_object(repo)?,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    match object.kind() {
+        Some(ObjectType::Tree) => {
+            let walk = TreeWalk {
+                git_dir: repo.path(),
+                root: Path::new(path),
+                counter,
+                cache,
+                selection,
+            };
+            let dir = get_tree_data(&walk, Dir::Tree(object.id()), path, &Ignores::default())?;
+            Ok(Some(PathData::Dir(dir)))
+        }
+        Some(ObjectType::Blob) => Ok(Some(count_file_blob(
+            &object.peel_to_blob()?,
+            path,
+            counter,
+        )?)),
+        _ => {
+            Err(git2::Error::from_str(&format!("{path} is neither a file nor a directory")).into())
+        }
+    }
+}
+
+/// Counts a blob given as the counted path, even if it is binary.
+fn count_file_blob(blob: &Blob, path: &str, counter: &Counter) -> Result<PathData> {
+    let language = Language::detect(path);
+    let counts = counter::count_bytes(blob.content(), language, counter.word_mode, true)
+        .expect("binary is included");
+    Ok(PathData::File(counter.file_data(
+        path.to_owned(),
+        language,
+        counts,
+    )))
+}
+
+/// The entries of the index grouped into directories like the trees of a commit, as the
+/// root directory. Conflicted files are left out, as they have no staged contents.
+fn index_entries(repo: &Repository) -> Result<Entry> {
+    enum Node {
+        File(Entry),
+        Dir(BTreeMap<String, Node>),
+    }
+
+    fn into_entry(name: String, dir: BTreeMap<String, Node>) -> Entry {
+        let children = dir
+            .into_iter()
+            .map(|(name, node)| match node {
+                Node::File(entry) => entry,
+                Node::Dir(dir) => into_entry(name, dir),
+            })
+            .collect();
+        Entry {
+            name,
+            id: Oid::ZERO_SHA1,
+            kind: Some(ObjectType::Tree),
+            mode: MODE_DIR,
+            children: Some(children),
+        }
+    }
+
+    let mut root = BTreeMap::new();
+    'entries: for entry in repo.index()?.iter() {
+        if entry.flags & INDEX_STAGE_MASK != 0 {
+            continue;
+        }
+        let path = match String::from_utf8(entry.path) {
+            Ok(path) => path,
+            Err(err) => {
+                let path = String::from_utf8_lossy(err.as_bytes()).into_owned();
+                return Err(Error::LcInvalidPathError(path.into()));
+            }
+        };
+        let mut names: Vec<&str> = path.split('/').collect();
+        let name = names.pop().unwrap_or_default();
+        let mut dir = &mut root;
+        for parent in names {
+            let node = dir
+                .entry(parent.to_owned())
+                .or_insert_with(|| Node::Dir(BTreeMap::new()));
+            dir = match node {
+                Node::Dir(dir) => dir,
+                // A file and a directory of the same name only exist in conflicted indexes
+                Node::File(_) => continue 'entries,
+            };
+        }
+        let mode = entry.mode as i32;
+        let kind = if mode == MODE_SUBMODULE {
+            ObjectType::Commit
+        } else {
+            ObjectType::Blob
+        };
+        dir.insert(
+            name.to_owned(),
+            Node::File(Entry {
+                name: name.to_owned(),
+                id: entry.id,
+                kind: Some(kind),
+                mode,
+                children: None,
+            }),
+        );
+    }
+    Ok(into_entry(String::new(), root))
+}
+
+/// The lines added and removed in every file below `in_repo` that differs between the trees,
+/// by the name the file is counted as.
+fn line_changes(
+    repo: &Repository,
+    old: &Tree,
+    new: &Tree,
+    in_repo: &Path,
+    path: &str,
+) -> Result<HashMap<String, (usize, usize)>> {
+    let mut options = DiffOptions::new();
+    if !in_repo.as_os_str().is_empty() {
+        options.pathspec(in_repo);
+    }
+    let diff = repo.diff_tree_to_tree(Some(old), Some(new), Some(&mut options))?;
+    let mut changes = HashMap::new();
+    for i in 0..diff.deltas().len() {
+        let patch = match Patch::from_diff(&diff, i)? {
+            Some(patch) => patch,
+            None => continue,
+        };
+        let file = match patch.delta().new_file().path() {
+            Some(file) => file.strip_prefix(in_repo).unwrap_or(file),
+            None => continue,
+        };
+        let name = if file.as_os_str().is_empty() {
+            path.to_owned()
+        } else {
+            Path::new(path).join(file).to_string_lossy().into_owned()
+        };
+        let (_, added, removed) = patch.line_stats()?;
+        changes.insert(name, (added, removed));
+    }
+    Ok(changes)
+}
+
+/// Opens the repository containing `path` and returns it together with the location of
+/// `path` in its working tree. `path` does not need to exist in the working tree.
+fn open(path: &str) -> Result<(Repository, PathBuf)> {
+    let absolute = normalize(&std::env::current_dir()?.join(path));
+    let existing = absolute
+        .ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("/"));
+    let missing = absolute.strip_prefix(existing).unwrap_or(Path::new(""));
+    let existing = std::fs::canonicalize(existing)?;
+
+    let repo = Repository::discover(&existing)?;
+    let workdir = match repo.workdir() {
+        Some(workdir) => std::fs::canonicalize(workdir)?,
+        None => return Err(git2::Error::from_str("the repository has no working tree").into()),
+    };
+    let in_repo = match existing.strip_prefix(&workdir) {
+        // Joining an empty path would append a slash
+        Ok(in_repo) if missing.as_os_str().is_empty() => in_repo.to_owned(),
+        Ok(in_repo) => in_repo.join(missing),
+        Err(_) => return Err(Error::LcInvalidPathError(absolute)),
+    };
+    Ok((repo, in_repo))
+}
+
+/// Resolves `.` and `..` in an absolute path without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Calls `f` with the repository at `git_dir`, which is opened once per thread.
+fn with_repo<T>(git_dir: &Path, f: impl FnOnce(&Repository) -> Result<T>) -> Result<T> {
+    let repo = REPO.with(|cell| {
+        let mut cached = cell.borrow_mut();
+        match &*cached {
+            Some(repo) if repo.path() == git_dir => Ok(Rc::clone(repo)),
+            _ => {
+                let repo = Rc::new(Repository::open(git_dir)?);
+                *cached = Some(Rc::clone(&repo));
+                Ok::<_, Error>(repo)
+            }
+        }
+    })?;
+    // The cell is not borrowed anymore, so `f` may walk subtrees on this thread
+    f(&repo)
+}
+
+/// Counts the tree `id` like [`crate::get_dir_data`] counts a directory, with the ignore
+/// files read from the tree.
+fn get_tree_data(walk: &TreeWalk, dir: Dir, dir_path: &str, ignores: &Ignores) -> Result<DirData> {
+    let (entries, ignore_files) = with_repo(walk.git_dir, |repo| {
+        let entries = match dir {
+            Dir::Tree(id) => {
+                let tree = repo.find_tree(id)?;
+                let mut entries = tree
+                    .iter()
+                    .map(|entry| match entry.name() {
+                        Ok(name) => Ok(Entry {
+                            name: name.to_owned(),
+                            id: entry.id(),
+                            kind: entry.kind(),
+                            mode: entry.filemode(),
+                            children: None,
+                        }),
+                        Err(_) => Err(Error::LcInvalidPathError(
+                            Path::new(dir_path)
+                                .join(String::from_utf8_lossy(entry.name_bytes()).as_ref()),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                // Trees sort directories as if their names ended with a slash
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+                Cow::Owned(entries)
+            }
+            // Sorted by name already
+            Dir::Index(entries) => Cow::Borrowed(entries),
+        };
+
+        let mut ignore_files = vec![];
+        for name in IGNORE_FILES {
+            if let Some(entry) = entries
+                .iter()
+                .find(|e| e.name == *name && e.kind == Some(ObjectType::Blob))
+            {
+                let blob = repo.find_blob(entry.id)?;
+                ignore_files.push((*name, String::from_utf8_lossy(blob.content()).into_owned()));
+            }
+        }
+        Ok((entries, ignore_files))
+    })?;
+    let ignores = ignores.enter_with(dir_path, &ignore_files)?;
+
+    let counted = entries
+        .par_iter()
+        .map(|entry| {
+            let path = Path::new(dir_path)
+                .join(&entry.name)
+                .to_string_lossy()
+                .into_owned();
+            match count_tree_entry(walk, entry, &path, &ignores) {
+                Err(error) if !walk.counter.fail_fast => {
+                    Ok(Some(Counted::Failed(FileError { path, error })))
+                }
+                counted => counted,
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut dir_data = DirData::new(dir_path);
+    for entry in counted.into_iter().flatten() {
+        dir_data.push(entry);
+    }
+    Ok(dir_data)
+}
+
+/// Counts a single entry of a tree, returning `None` for symbolic links, submodules and
+/// trees that are not descended into.
+fn count_tree_entry(
+    walk: &TreeWalk,
+    entry: &Entry,
+    path: &str,
+    ignores: &Ignores,
+) -> Result<Option<Counted>> {
+    if entry.name == ".lcignore" {
+        return Ok(None);
+    }
+    let counter = walk.counter;
+    let relative = Path::new(path)
+        .strip_prefix(walk.root)
+        .unwrap_or(Path::new(path));
+    let hidden = (!counter.hidden && entry.name.starts_with('.')).then_some(Exclusion::Hidden);
+    let excluded = |reason| {
+        Ok(Some(Counted::Excluded(ExcludedFile {
+            file_name: path.to_owned(),
+            reason,
+        })))
+    };
+
+    let selected = |contains: fn(&Selection, &Path) -> bool| {
+        walk.selection
+            .is_none_or(|selection| contains(selection, relative))
+    };
+
+    match entry.kind {
+        Some(ObjectType::Tree) => {
+            if !selected(Selection::contains_dir) {
+                return Ok(None);
+            }
+            let depth = relative.components().count();
+            if !counter.recursive || counter.max_depth.is_some_and(|max| depth > max) {
+                return Ok(None);
+            }
+            let exclusion = hidden
+                .or_else(|| ignores.check(path, true))
+                .or_else(|| counter.filters.check_dir(relative));
+            if let Some(reason) = exclusion {
+                return excluded(reason);
+            }
+            let dir = match &entry.children {
+                Some(children) => Dir::Index(children),
+                None => Dir::Tree(entry.id),
+            };
+            let data = get_tree_data(walk, dir, path, ignores)?;
+            Ok(Some(Counted::Dir(data)))
+        }
+        Some(ObjectType::Blob) if entry.mode != MODE_LINK => {
+            if !selected(Selection::contains_file) {
+                return Ok(None);
+            }
+            let size = with_repo(walk.git_dir, |repo| {
+                Ok(repo.odb()?.read_header(entry.id)?.0)
+            })?;
+            let size = size as u64;
+            let exclusion = hidden
+                .or_else(|| ignores.check(path, false))
+                .or_else(|| counter.filters.check_file(relative, size));
+            if let Some(reason) = exclusion {
+                return excluded(reason);
+            }
+            let language = Language::detect(path);
+            Ok(Some(match count_blob(walk, entry.id, language)? {
+                Some(counts) => Counted::File(counter.file_data(path.to_owned(), language, counts)),
+                None => Counted::SkippedBinary(SkippedFile {
+                    file_name: path.to_owned(),
+                    size,
+                }),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Counts a blob, or looks up its counts if it was counted as `language` before.
+fn count_blob(
+    walk: &TreeWalk,
+    id: Oid,
+    language: Option<&'static Language>,
+) -> Result<Option<Counts>> {
+    let key = (id, language.map(|lang| lang.name));
+    let cache = &walk.cache.0;
+    let cached = cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+        .copied();
+    if let Some(counts) = cached {
+        return Ok(counts);
+    }
+    let counter = walk.counter;
+    let counts = with_repo(walk.git_dir, |repo| {
+        let blob = repo.find_blob(id)?;
+        Ok(counter::count_bytes(
+            blob.content(),
+            language,
+            counter.word_mode,
+            counter.include_binary,
+        ))
+    })?;
+    cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, counts);
+    Ok(counts)
+}
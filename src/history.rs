@@ -0,0 +1,67 @@
+//! Line counts over the history of a git repository.
+
+use crate::{FileError, LanguageSummary, Totals};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Which commits of the history are counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sample {
+    /// Every commit
+    #[default]
+    Commit,
+    /// The last commit of every day, in UTC
+    Day,
+    /// The last commit of every week from Monday to Sunday, in UTC
+    Week,
+    /// Only commits with a tag
+    Tag,
+}
+
+impl Sample {
+    /// The period of a commit made at `time`, in seconds since the Unix epoch. Only one
+    /// commit is counted per period. `None` if commits are not sampled by time.
+    pub(crate) fn period(self, time: i64) -> Option<i64> {
+        let day = time.div_euclid(SECONDS_PER_DAY);
+        match self {
+            Sample::Day => Some(day),
+            // 1970-01-01 was a Thursday
+            Sample::Week => Some((day + 3).div_euclid(7)),
+            Sample::Commit | Sample::Tag => None,
+        }
+    }
+}
+
+/// The counts of a path at one commit.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// The full hash of the commit
+    pub commit: String,
+    /// The commit time in seconds since the Unix epoch
+    pub time: i64,
+    /// The tags pointing to the commit, sorted by name
+    pub tags: Vec<String>,
+    pub totals: Totals,
+    pub languages: Vec<LanguageSummary>,
+    /// Files and directories that could not be counted
+    pub errors: Vec<FileError>,
+}
+
+impl Snapshot {
+    /// The UTC date of the commit, e.g. `2024-05-31`.
+    pub fn date(&self) -> String {
+        // Days to civil dates as described in http://howardhinnant.github.io/date_algorithms.html
+        let days = self.time.div_euclid(SECONDS_PER_DAY) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Months starting in March, so that leap days come last
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+}
@@ -0,0 +1,85 @@
+use crate::filter::Exclusion;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Names of the files that contain ignore patterns, from lowest to highest precedence.
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".lcignore"];
+
+/// The ignore rules of a directory and all of its parents.
+///
+/// Patterns follow the `.gitignore` syntax. Rules of deeper directories take precedence
+/// over the ones of their parents, and within one directory `.lcignore` beats `.ignore`
+/// which beats `.gitignore`.
+#[derive(Clone, Default)]
+pub struct Ignores {
+    /// One matcher per directory level, the innermost directory last
+    levels: Vec<Arc<Gitignore>>,
+}
+
+impl Ignores {
+    /// Returns the rules for `dir`, made up of these rules and the ignore files within `dir`.
+    pub fn enter(&self, dir: impl AsRef<Path>) -> Result<Ignores, ignore::Error> {
+        let dir = dir.as_ref();
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in IGNORE_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                found = true;
+                if let Some(err) = builder.add(path) {
+                    return Err(err);
+                }
+            }
+        }
+
+        self.push(builder, found)
+    }
+
+    /// Like [`Ignores::enter`], but with the ignore files of `dir` given as their names and
+    /// contents, in the order of [`IGNORE_FILES`], instead of being read from disk.
+    pub fn enter_with(
+        &self,
+        dir: impl AsRef<Path>,
+        files: &[(&str, String)],
+    ) -> Result<Ignores, ignore::Error> {
+        let dir = dir.as_ref();
+        let mut builder = GitignoreBuilder::new(dir);
+        for (name, contents) in files {
+            let from = dir.join(name);
+            for line in contents.lines() {
+                builder.add_line(Some(from.clone()), line)?;
+            }
+        }
+        self.push(builder, !files.is_empty())
+    }
+
+    fn push(&self, builder: GitignoreBuilder, found: bool) -> Result<Ignores, ignore::Error> {
+        let mut ignores = self.clone();
+        if found {
+            ignores.levels.push(Arc::new(builder.build()?));
+        }
+        Ok(ignores)
+    }
+
+    /// Checks whether `path` is excluded by any of the rules, returning the reason if it is.
+    /// The last matching pattern of the innermost directory decides, so `!pattern` can
+    /// re-include a path.
+    pub fn check(&self, path: impl AsRef<Path>, is_dir: bool) -> Option<Exclusion> {
+        let path = path.as_ref();
+        for level in self.levels.iter().rev() {
+            match level.matched(path, is_dir) {
+                Match::Ignore(glob) => {
+                    return Some(Exclusion::Ignored {
+                        pattern: glob.original().to_owned(),
+                        file: glob.from().map(Path::to_path_buf),
+                    })
+                }
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,396 @@
+//! JSON output of the counted tree and of `lc diff` and `lc history`.
+//!
+//! The layout of the document is described in the README and versioned by
+//! [`SCHEMA_VERSION`]; any change that is not purely additive bumps the version.
+
+use lc::diff::{DirDiff, FileDiff, LineDelta};
+use lc::history::Snapshot;
+use lc::{
+    summarize_authors, summarize_languages, AuthorLines, DirData, ExcludedFile, FileData,
+    FileError, LanguageSummary, PathData, SkippedFile, Totals,
+};
+use serde::Serialize;
+use std::io::Write;
+
+/// Version of the JSON document layout.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    schema_version: u32,
+    /// One node per counted path
+    roots: Vec<Node<'a>>,
+    /// Totals over every counted file
+    totals: Totals,
+    languages: Vec<LanguageSummary>,
+    /// Lines per author over every counted file, only with `--by-author`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<AuthorLines>,
+    /// Binary files that were skipped anywhere
+    skipped_binary: Vec<JsonSkipped<'a>>,
+    /// Paths left out by filters or ignore files anywhere
+    excluded: Vec<JsonExcluded<'a>>,
+    /// Paths that could not be counted, including the ones anywhere in the trees
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Node<'a> {
+    Dir(JsonDir<'a>),
+    File(JsonFile<'a>),
+}
+
+#[derive(Serialize)]
+struct JsonDir<'a> {
+    path: &'a str,
+    /// Totals of the files directly inside this directory
+    totals: Totals,
+    /// Totals of the files in this directory and all of its subdirectories
+    recursive_totals: Totals,
+    /// Lines per author in this directory and all of its subdirectories, only with `--by-author`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<AuthorLines>,
+    files: Vec<JsonFile<'a>>,
+    dirs: Vec<JsonDir<'a>>,
+    skipped_binary: Vec<JsonSkipped<'a>>,
+    excluded: Vec<JsonExcluded<'a>>,
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFile<'a> {
+    path: &'a str,
+    language: Option<&'static str>,
+    bytes: usize,
+    lines: usize,
+    characters: usize,
+    graphemes: usize,
+    visible: usize,
+    words: usize,
+    code: usize,
+    comments: usize,
+    blanks: usize,
+    binary: bool,
+    /// Only with `--by-author`
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    authors: &'a [AuthorLines],
+}
+
+#[derive(Serialize)]
+struct JsonSkipped<'a> {
+    path: &'a str,
+    size: u64,
+}
+
+impl<'a> From<&'a SkippedFile> for JsonSkipped<'a> {
+    fn from(file: &'a SkippedFile) -> Self {
+        Self {
+            path: &file.file_name,
+            size: file.size,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonExcluded<'a> {
+    path: &'a str,
+    reason: String,
+}
+
+impl<'a> From<&'a ExcludedFile> for JsonExcluded<'a> {
+    fn from(file: &'a ExcludedFile) -> Self {
+        Self {
+            path: &file.file_name,
+            reason: file.reason.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    path: &'a str,
+    error: String,
+}
+
+impl<'a> From<&'a FileError> for JsonError<'a> {
+    fn from(error: &'a FileError) -> Self {
+        Self {
+            path: &error.path,
+            error: error.error.to_string(),
+        }
+    }
+}
+
+impl<'a> From<&'a FileData> for JsonFile<'a> {
+    fn from(file: &'a FileData) -> Self {
+        Self {
+            path: &file.file_name,
+            language: file.language.map(|lang| lang.name),
+            bytes: file.bytes,
+            lines: file.lines,
+            characters: file.characters,
+            graphemes: file.graphemes,
+            visible: file.visible,
+            words: file.words,
+            code: file.code,
+            comments: file.comments,
+            blanks: file.blanks,
+            binary: file.binary,
+            authors: &file.authors,
+        }
+    }
+}
+
+impl<'a> From<&'a DirData> for JsonDir<'a> {
+    fn from(dir: &'a DirData) -> Self {
+        Self {
+            path: &dir.dir_name,
+            totals: dir.direct_totals(),
+            recursive_totals: dir.recursive_totals(),
+            authors: dir.author_summary(),
+            files: dir.file_data.iter().map(JsonFile::from).collect(),
+            dirs: dir.sub_dirs.iter().map(JsonDir::from).collect(),
+            skipped_binary: dir.skipped_binary.iter().map(JsonSkipped::from).collect(),
+            excluded: dir.excluded.iter().map(JsonExcluded::from).collect(),
+            errors: dir.errors.iter().map(JsonError::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a PathData> for Node<'a> {
+    fn from(data: &'a PathData) -> Self {
+        match data {
+            PathData::File(file) => Node::File(file.into()),
+            PathData::Dir(dir) => Node::Dir(dir.into()),
+        }
+    }
+}
+
+/// Writes the report of all counted paths, with `errors` being the paths that could not be
+/// counted at all.
+pub fn write_paths(
+    out: impl Write,
+    paths: &[PathData],
+    errors: &[FileError],
+) -> serde_json::Result<()> {
+    let mut totals = Totals::default();
+    for path in paths {
+        totals += path.recursive_totals();
+    }
+    write_report(
+        out,
+        Report {
+            schema_version: SCHEMA_VERSION,
+            roots: paths.iter().map(Node::from).collect(),
+            totals,
+            languages: summarize_languages(paths.iter().flat_map(PathData::all_files)),
+            authors: summarize_authors(paths.iter().flat_map(PathData::all_files)),
+            skipped_binary: paths
+                .iter()
+                .flat_map(PathData::all_skipped_binary)
+                .map(JsonSkipped::from)
+                .collect(),
+            excluded: paths
+                .iter()
+                .flat_map(PathData::all_excluded)
+                .map(JsonExcluded::from)
+                .collect(),
+            errors: errors
+                .iter()
+                .chain(paths.iter().flat_map(PathData::all_errors))
+                .map(JsonError::from)
+                .collect(),
+        },
+    )
+}
+
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    schema_version: u32,
+    old: &'a str,
+    new: &'a str,
+    /// One node per compared path
+    roots: Vec<JsonDirDiff<'a>>,
+    /// Delta over every compared file
+    totals: JsonDelta,
+    languages: Vec<JsonLanguageDiff>,
+    /// Paths that could not be compared, including the ones anywhere in the trees
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDelta {
+    before: usize,
+    after: usize,
+    added: usize,
+    removed: usize,
+    net: i64,
+}
+
+impl From<LineDelta> for JsonDelta {
+    fn from(delta: LineDelta) -> Self {
+        Self {
+            before: delta.before,
+            after: delta.after,
+            added: delta.added,
+            removed: delta.removed,
+            net: delta.net(),
+        }
+    }
+}
+
+/// A directory of `lc diff` with only the files and subdirectories that changed.
+#[derive(Serialize)]
+struct JsonDirDiff<'a> {
+    path: &'a str,
+    /// Delta of the files directly inside this directory
+    totals: JsonDelta,
+    /// Delta of the files in this directory and all of its subdirectories
+    recursive_totals: JsonDelta,
+    files: Vec<JsonFileDiff<'a>>,
+    dirs: Vec<JsonDirDiff<'a>>,
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFileDiff<'a> {
+    path: &'a str,
+    language: Option<&'static str>,
+    #[serde(flatten)]
+    lines: JsonDelta,
+}
+
+#[derive(Serialize)]
+struct JsonLanguageDiff {
+    name: &'static str,
+    #[serde(flatten)]
+    lines: JsonDelta,
+}
+
+impl<'a> From<&'a FileDiff> for JsonFileDiff<'a> {
+    fn from(file: &'a FileDiff) -> Self {
+        Self {
+            path: &file.file_name,
+            language: file.language.map(|lang| lang.name),
+            lines: file.lines.into(),
+        }
+    }
+}
+
+impl<'a> From<&'a DirDiff> for JsonDirDiff<'a> {
+    fn from(dir: &'a DirDiff) -> Self {
+        Self {
+            path: &dir.dir_name,
+            totals: dir.direct_totals().into(),
+            recursive_totals: dir.recursive_totals().into(),
+            files: dir
+                .files
+                .iter()
+                .filter(|file| file.lines.is_changed())
+                .map(JsonFileDiff::from)
+                .collect(),
+            dirs: dir
+                .sub_dirs
+                .iter()
+                .filter(|dir| dir.recursive_totals().is_changed())
+                .map(JsonDirDiff::from)
+                .collect(),
+            errors: dir.errors.iter().map(JsonError::from).collect(),
+        }
+    }
+}
+
+/// Writes the report of `lc diff` between the revisions `old` and `new`, with `errors` being
+/// the paths that could not be compared at all.
+pub fn write_diffs(
+    mut out: impl Write,
+    old: &str,
+    new: &str,
+    diffs: &[DirDiff],
+    errors: &[FileError],
+) -> serde_json::Result<()> {
+    let mut totals = LineDelta::default();
+    for diff in diffs {
+        totals += diff.recursive_totals();
+    }
+    let report = DiffReport {
+        schema_version: SCHEMA_VERSION,
+        old,
+        new,
+        roots: diffs.iter().map(JsonDirDiff::from).collect(),
+        totals: totals.into(),
+        languages: lc::diff::summarize_languages(diffs.iter().flat_map(DirDiff::all_files))
+            .into_iter()
+            .filter(|lang| lang.lines.is_changed())
+            .map(|lang| JsonLanguageDiff {
+                name: lang.name,
+                lines: lang.lines.into(),
+            })
+            .collect(),
+        errors: errors
+            .iter()
+            .chain(diffs.iter().flat_map(DirDiff::all_errors))
+            .map(JsonError::from)
+            .collect(),
+    };
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out).map_err(serde_json::Error::io)
+}
+
+#[derive(Serialize)]
+struct HistoryReport<'a> {
+    schema_version: u32,
+    rev: &'a str,
+    path: &'a str,
+    /// One entry per counted commit, oldest first
+    commits: Vec<JsonSnapshot<'a>>,
+    /// Paths that could not be counted in any of the commits
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonSnapshot<'a> {
+    commit: &'a str,
+    date: String,
+    /// Seconds since the Unix epoch
+    time: i64,
+    tags: &'a [String],
+    totals: &'a Totals,
+    languages: &'a [LanguageSummary],
+}
+
+/// Writes the report of `lc history` of `path` at the revision `rev`, with `errors` being the
+/// paths that could not be counted in some of the commits.
+pub fn write_history(
+    mut out: impl Write,
+    rev: &str,
+    path: &str,
+    snapshots: &[Snapshot],
+    errors: &[&FileError],
+) -> serde_json::Result<()> {
+    let report = HistoryReport {
+        schema_version: SCHEMA_VERSION,
+        rev,
+        path,
+        commits: snapshots
+            .iter()
+            .map(|snapshot| JsonSnapshot {
+                commit: &snapshot.commit,
+                date: snapshot.date(),
+                time: snapshot.time,
+                tags: &snapshot.tags,
+                totals: &snapshot.totals,
+                languages: &snapshot.languages,
+            })
+            .collect(),
+        errors: errors.iter().copied().map(JsonError::from).collect(),
+    };
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out).map_err(serde_json::Error::io)
+}
+
+fn write_report(mut out: impl Write, report: Report) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out).map_err(serde_json::Error::io)
+}
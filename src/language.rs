@@ -0,0 +1,431 @@
+//! Detection of languages and classification of their lines.
+
+use std::path::Path;
+
+/// A language lc knows how to recognise and split into code, comment and blank lines.
+#[derive(Debug)]
+pub struct Language {
+    /// Name of the language as shown in the summary
+    pub name: &'static str,
+    /// File extensions (without the leading dot) belonging to this language
+    pub extensions: &'static [&'static str],
+    /// Exact file names belonging to this language, e.g. "Makefile"
+    pub file_names: &'static [&'static str],
+    /// Tokens that start a comment running until the end of the line
+    pub line_comments: &'static [&'static str],
+    /// Pairs of tokens that open and close a block comment
+    pub block_comments: &'static [(&'static str, &'static str)],
+    /// Tokens that start and end a string literal, within which comment tokens are ignored
+    pub quotes: &'static [&'static str],
+}
+
+const C_LINE: &[&str] = &["//"];
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const HASH_LINE: &[&str] = &["#"];
+const XML_BLOCK: &[(&str, &str)] = &[("<!--", "-->")];
+const DOUBLE_QUOTES: &[&str] = &["\""];
+// Single quotes also delimit character literals, which may contain a double quote
+const QUOTES: &[&str] = &["\"", "'"];
+const JS_QUOTES: &[&str] = &["\"", "'", "`"];
+
+/// Every language lc can detect, looked up by [`Language::detect`].
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        extensions: &["c"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "C Header",
+        extensions: &["h"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "C#",
+        extensions: &["cs"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Dockerfile",
+        extensions: &["dockerfile"],
+        file_names: &["Dockerfile"],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: JS_QUOTES,
+    },
+    Language {
+        name: "Haskell",
+        extensions: &["hs"],
+        file_names: &[],
+        line_comments: &["--"],
+        block_comments: &[("{-", "-}")],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "HTML",
+        extensions: &["htm", "html"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: XML_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["cjs", "js", "jsx", "mjs"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: JS_QUOTES,
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "Kotlin",
+        extensions: &["kt", "kts"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Lua",
+        extensions: &["lua"],
+        file_names: &[],
+        line_comments: &["--"],
+        block_comments: &[("--[[", "]]")],
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Makefile",
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "PHP",
+        extensions: &["php"],
+        file_names: &[],
+        line_comments: &["//", "#"],
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyw"],
+        file_names: &[],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        file_names: &["Gemfile", "Rakefile"],
+        line_comments: HASH_LINE,
+        block_comments: &[("=begin", "=end")],
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["bash", "sh", "zsh"],
+        file_names: &[],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: QUOTES,
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        file_names: &[],
+        line_comments: &["--"],
+        block_comments: C_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "Swift",
+        extensions: &["swift"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "Text",
+        extensions: &["txt"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[],
+        quotes: DOUBLE_QUOTES,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        file_names: &["Cargo.lock"],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: QUOTES,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx"],
+        file_names: &[],
+        line_comments: C_LINE,
+        block_comments: C_BLOCK,
+        quotes: JS_QUOTES,
+    },
+    Language {
+        name: "XML",
+        extensions: &["svg", "xml"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: XML_BLOCK,
+        quotes: QUOTES,
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yaml", "yml"],
+        file_names: &[],
+        line_comments: HASH_LINE,
+        block_comments: &[],
+        quotes: QUOTES,
+    },
+];
+
+impl Language {
+    /// Detects the language of a file by its name first and its extension second.
+    pub fn detect(path: impl AsRef<Path>) -> Option<&'static Language> {
+        let path = path.as_ref();
+        let file_name = path.file_name()?.to_str()?;
+        if let Some(lang) = LANGUAGES.iter().find(|l| l.file_names.contains(&file_name)) {
+            return Some(lang);
+        }
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|l| l.extensions.contains(&extension.as_str()))
+    }
+}
+
+/// What a single line of a file consists of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// The line contains code, possibly followed by a comment
+    Code,
+    /// The line contains nothing but comments
+    Comment,
+    /// The line contains nothing but whitespace
+    Blank,
+}
+
+/// Classifies lines one after another, remembering whether a block comment is still open.
+pub struct Classifier {
+    language: Option<&'static Language>,
+    /// The closing token of the block comment we are currently in
+    open_block: Option<&'static str>,
+}
+
+impl Classifier {
+    /// Creates a classifier for the lines of a file. Files of unknown languages have no comments.
+    pub fn new(language: Option<&'static Language>) -> Self {
+        Self {
+            language,
+            open_block: None,
+        }
+    }
+
+    /// Classifies the next line of the file. Lines containing any code count as code.
+    pub fn classify(&mut self, line: &str) -> LineKind {
+        let mut rest = line.trim();
+        if rest.is_empty() {
+            return LineKind::Blank;
+        }
+        let lang = match self.language {
+            Some(lang) => lang,
+            None => return LineKind::Code,
+        };
+
+        let mut has_code = false;
+        let mut has_comment = false;
+        while !rest.is_empty() {
+            if let Some(end) = self.open_block {
+                has_comment = true;
+                match rest.find(end) {
+                    Some(idx) => {
+                        rest = rest[idx + end.len()..].trim_start();
+                        self.open_block = None;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+            // Block comments first, as their openers may start with a line comment like `--[[`
+            if let Some((start, end)) = lang
+                .block_comments
+                .iter()
+                .find(|(s, _)| rest.starts_with(s))
+            {
+                has_comment = true;
+                rest = &rest[start.len()..];
+                self.open_block = Some(end);
+                continue;
+            }
+            if lang.line_comments.iter().any(|c| rest.starts_with(c)) {
+                has_comment = true;
+                break;
+            }
+            has_code = true;
+            rest = skip_token(rest, lang.quotes).trim_start();
+        }
+
+        if has_code {
+            LineKind::Code
+        } else if has_comment {
+            LineKind::Comment
+        } else {
+            LineKind::Blank
+        }
+    }
+}
+
+/// Skips a single character of code, or a whole string literal delimited by one of `quotes`
+/// so that comment tokens inside of it are not mistaken for comments.
+fn skip_token<'a>(code: &'a str, quotes: &[&str]) -> &'a str {
+    if let Some(quote) = quotes.iter().find(|quote| code.starts_with(**quote)) {
+        let mut escaped = false;
+        let literal = &code[quote.len()..];
+        for (idx, c) in literal.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                _ if !escaped && literal[idx..].starts_with(quote) => {
+                    return &literal[idx + quote.len()..];
+                }
+                _ => escaped = false,
+            }
+        }
+        return "";
+    }
+    let mut chars = code.chars();
+    chars.next();
+    chars.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LineKind::*;
+
+    fn classify(file_name: &str, lines: &[&str]) -> Vec<LineKind> {
+        let mut classifier = Classifier::new(Language::detect(file_name));
+        lines.iter().map(|line| classifier.classify(line)).collect()
+    }
+
+    #[test]
+    fn block_comment_opener_starting_with_line_comment() {
+        let kinds = classify("x.lua", &["--[[", "local x = 1", "]]", "print(1)"]);
+        assert_eq!(kinds, [Comment, Comment, Comment, Code]);
+    }
+
+    #[test]
+    fn comment_tokens_in_strings() {
+        let kinds = classify(
+            "x.rs",
+            &[
+                r#"let url = "http://example.com";"#,
+                r#"let s = "/* not a comment";"#,
+                "x();",
+            ],
+        );
+        assert_eq!(kinds, [Code; 3]);
+        let kinds = classify(
+            "x.js",
+            &[
+                "const s = '/*';",
+                "files: ['src/**/*.ts', '**/*.js', `**/*.jsx`];",
+                r#"const q = '"'; // "quoted""#,
+                "foo();",
+            ],
+        );
+        assert_eq!(kinds, [Code; 4]);
+    }
+
+    #[test]
+    fn block_comment_ending_partway_through_a_line() {
+        let kinds = classify(
+            "x.c",
+            &[
+                "/* start",
+                "still comment */ int x = 1;",
+                "/* a */ /* b */",
+                "int y; /* c",
+            ],
+        );
+        assert_eq!(kinds, [Comment, Code, Comment, Code]);
+        let kinds = classify("x.c", &["/* one */", "// two", "", "three();"]);
+        assert_eq!(kinds, [Comment, Comment, Blank, Code]);
+    }
+}
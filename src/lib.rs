@@ -0,0 +1,955 @@
+//! Counting of lines, characters and words in files and directory trees.
+//!
+//! Everything starts with a [`Counter`], which holds the counting options and counts
+//! paths, readers or byte slices:
+//!
+//! ```no_run
+//! use lc::{Counter, PathData};
+//!
+//! let counter = Counter::new().recursive(true).skip_empty_lines(true);
+//! if let PathData::Dir(dir) = counter.count_path("src")? {
+//!     println!("{} lines", dir.recursive_totals().lines);
+//! }
+//!
+//! let file = counter.count_bytes("main.rs", b"fn main() {}\n");
+//! assert_eq!(file.code, 1);
+//! # Ok::<(), lc::Error>(())
+//! ```
+
+mod counter;
+pub mod diff;
+pub mod filter;
+mod git;
+pub mod history;
+mod ignores;
+pub mod language;
+mod words;
+
+use counter::Counts;
+use diff::DirDiff;
+use filter::{Exclusion, Filters};
+use history::{Sample, Snapshot};
+use ignores::Ignores;
+use language::Language;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+pub use git::GitFiles;
+pub use words::WordMode;
+
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    #[error("Error occurred while reading file: {0}")]
+    LcIoError(#[from] std::io::Error),
+
+    #[error("Path is not valid UTF-8: {0:?}")]
+    LcInvalidPathError(std::path::PathBuf),
+
+    #[error("File system loop, {0:?} points to one of its parent directories")]
+    LcSymlinkLoopError(std::path::PathBuf),
+
+    #[error("Invalid ignore file: {0}")]
+    LcIgnoreError(#[from] ignore::Error),
+
+    #[error("Could not start the counting threads: {0}")]
+    LcThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+
+    #[error("Git error: {0}")]
+    LcGitError(#[from] git2::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Counts files and directories with the configured options.
+///
+/// All options are off by default; directories are counted with one thread per CPU.
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    skip_empty_lines: bool,
+    word_mode: WordMode,
+    recursive: bool,
+    max_depth: Option<usize>,
+    threads: usize,
+    mmap: bool,
+    include_binary: bool,
+    fail_fast: bool,
+    filters: Filters,
+    hidden: bool,
+    follow_symlinks: bool,
+    one_file_system: bool,
+    by_author: bool,
+    git_files: GitFiles,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves lines containing only whitespace out of [`FileData::lines`].
+    pub fn skip_empty_lines(mut self, skip_empty_lines: bool) -> Self {
+        self.skip_empty_lines = skip_empty_lines;
+        self
+    }
+
+    /// How words are told apart, by whitespace like `wc` by default.
+    pub fn word_mode(mut self, word_mode: WordMode) -> Self {
+        self.word_mode = word_mode;
+        self
+    }
+
+    /// Counts subdirectories as well.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// When counting recursively, only descends `max_depth` levels of subdirectories
+    /// below the counted directory; `None` descends without a limit.
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Number of threads used for counting directories, 0 uses one thread per CPU.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Memory maps files of 64 MiB or more instead of reading them in chunks.
+    ///
+    /// If another process truncates a file while it is mapped, e.g. when rotating a log,
+    /// the process is killed by `SIGBUS` on Unix, so only use this for files that are not
+    /// being written to.
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Counts binary files found in directories instead of skipping them.
+    pub fn include_binary(mut self, include_binary: bool) -> Self {
+        self.include_binary = include_binary;
+        self
+    }
+
+    /// Returns the first error that occurs while counting a directory instead of
+    /// recording it in [`DirData::errors`].
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Only counts the files of directories that pass `filters`.
+    pub fn filters(mut self, filters: Filters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Counts hidden files and directories, whose names start with a `.`, instead of
+    /// skipping them.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Follows symbolic links to files and directories instead of skipping them.
+    /// Links pointing to one of their parent directories are reported as errors (on Unix).
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Skips directories on other file systems than the counted directory (on Unix).
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+
+    /// Attributes the lines of every file counted in the working tree to the authors of their
+    /// last change in [`FileData::authors`], using the blame of the git repository containing
+    /// the file and its mailmap. Lines that are not committed yet, including the lines of files
+    /// outside of any repository, are attributed to [`NOT_COMMITTED`]. Has no effect on files
+    /// counted from a commit or a reader.
+    pub fn by_author(mut self, by_author: bool) -> Self {
+        self.by_author = by_author;
+        self
+    }
+
+    /// Only counts the files of directories that are tracked, staged or changed in the git
+    /// repository containing them. Files given directly are counted either way, and with
+    /// [`GitFiles::Staged`] everything is read from the index instead of the working tree.
+    pub fn git_files(mut self, git_files: GitFiles) -> Self {
+        self.git_files = git_files;
+        self
+    }
+
+    /// Counts a file or directory. Files given directly are counted even if they are binary.
+    pub fn count_path(&self, path: &str) -> Result<PathData> {
+        if self.git_files == GitFiles::Staged {
+            git::count_staged(self, path)
+        } else if std::fs::metadata(path)?.is_dir() {
+            Ok(PathData::Dir(self.count_dir(path)?))
+        } else {
+            let file = get_file_data(path, self, true)?.expect("binary files are included");
+            Ok(PathData::File(file))
+        }
+    }
+
+    /// Counts the files of a directory, and its subdirectories if counting recursively.
+    pub fn count_dir(&self, path: &str) -> Result<DirData> {
+        let selection = match &self.git_files {
+            GitFiles::All => None,
+            GitFiles::Staged => {
+                return match git::count_staged(self, path)? {
+                    PathData::Dir(dir) => Ok(dir),
+                    PathData::File(_) => {
+                        let message = format!("{path} is not a directory in the index");
+                        Err(git2::Error::from_str(&message).into())
+                    }
+                }
+            }
+            files => Some(git::select(path, files)?),
+        };
+        let pool = self.thread_pool()?;
+        let mut walk = Walk::new(Path::new(path), self)?;
+        walk.selection = selection.as_ref();
+        pool.install(|| get_dir_data(path, &walk, self, &Ignores::default()))
+    }
+
+    /// Counts exactly the given files, grouped into a tree of their directories below `.`.
+    ///
+    /// Ignore files are not consulted, but the [`Counter::filters`] are. Directories in the
+    /// list are skipped and binary files are skipped unless [`Counter::include_binary`] is set.
+    pub fn count_files<S: AsRef<str> + Sync>(&self, paths: &[S]) -> Result<DirData> {
+        let pool = self.thread_pool()?;
+        let counted = pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| {
+                    let path = path.as_ref();
+                    let counted = match count_listed_file(path, self) {
+                        Err(error) if !self.fail_fast => Ok(Some(Counted::Failed(FileError {
+                            path: path.to_owned(),
+                            error,
+                        }))),
+                        counted => counted,
+                    };
+                    counted.map(|counted| counted.map(|counted| (path, counted)))
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut root = DirData::new(".");
+        for (path, entry) in counted.into_iter().flatten() {
+            let parent = Path::new(path).parent().unwrap_or(".".as_ref());
+            root.sub_dir_mut(parent).push(entry);
+        }
+        root.sort();
+        Ok(root)
+    }
+
+    /// Counts `path` as it is in the commit `rev` of the git repository containing it,
+    /// reading the files from the object database instead of the working tree.
+    ///
+    /// `rev` is anything `git rev-parse` understands, like `HEAD~3`, `v1.0` or a commit hash,
+    /// and `path` does not need to exist in the working tree anymore. Directories are counted
+    /// like with [`Counter::count_dir`], with the ignore files read from the commit.
+    /// Symbolic links and submodules are skipped.
+    pub fn count_rev(&self, rev: &str, path: &str) -> Result<PathData> {
+        git::count_rev(self, rev, path)
+    }
+
+    /// Counts `path` in the commits `old` and `new` of its git repository like
+    /// [`Counter::count_rev`] and compares the two counts. The lines added and removed
+    /// per file are taken from the diff of the commits, without detecting renames.
+    ///
+    /// A file is compared as the only file of its parent directory, and a path that only
+    /// exists in one of the commits counts as empty in the other.
+    pub fn diff_revs(&self, old: &str, new: &str, path: &str) -> Result<DirDiff> {
+        git::diff_revs(self, old, new, path)
+    }
+
+    /// Counts `path` at the first-parent commits leading to `rev` in its git repository,
+    /// oldest commit first, like [`Counter::count_rev`] counts a single commit.
+    ///
+    /// Blobs are only counted once, no matter in how many commits they appear, so counting
+    /// thousands of commits mostly costs walking their trees.
+    pub fn history(&self, rev: &str, path: &str, sample: Sample) -> Result<Vec<Snapshot>> {
+        git::history(self, rev, path, sample)
+    }
+
+    /// Counts everything read from `reader`. The language is detected from `file_name`.
+    pub fn count_reader(
+        &self,
+        file_name: impl Into<String>,
+        reader: impl Read,
+    ) -> Result<FileData> {
+        let file_name = file_name.into();
+        let language = Language::detect(&file_name);
+        let counts = counter::count_reader(reader, language, self.word_mode, true)?
+            .expect("binary is included");
+        Ok(self.file_data(file_name, language, counts))
+    }
+
+    /// Counts contents that are already in memory. The language is detected from `file_name`.
+    pub fn count_bytes(&self, file_name: impl Into<String>, bytes: &[u8]) -> FileData {
+        let file_name = file_name.into();
+        let language = Language::detect(&file_name);
+        let counts = counter::count_bytes(bytes, language, self.word_mode, true)
+            .expect("binary is included");
+        self.file_data(file_name, language, counts)
+    }
+
+    fn thread_pool(&self) -> Result<rayon::ThreadPool> {
+        Ok(rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()?)
+    }
+
+    fn file_data(
+        &self,
+        file_name: String,
+        language: Option<&'static Language>,
+        counts: Counts,
+    ) -> FileData {
+        let lines = if self.skip_empty_lines {
+            counts.lines - counts.empty_lines
+        } else {
+            counts.lines
+        };
+
+        FileData {
+            file_name,
+            language,
+            bytes: counts.bytes,
+            lines,
+            newlines: counts.newlines,
+            characters: counts.characters,
+            graphemes: counts.graphemes,
+            visible: counts.visible,
+            invalid: counts.invalid,
+            words: counts.words,
+            code: counts.code,
+            comments: counts.comments,
+            blanks: counts.blanks,
+            binary: counts.binary,
+            authors: vec![],
+        }
+    }
+}
+
+/// The result of counting a path, which is either a file or a directory.
+#[derive(Debug)]
+pub enum PathData {
+    File(FileData),
+    Dir(DirData),
+}
+
+impl PathData {
+    /// Totals of the file, or of all files in the directory and its subdirectories.
+    pub fn recursive_totals(&self) -> Totals {
+        match self {
+            PathData::File(file) => Totals::of([file]),
+            PathData::Dir(dir) => dir.recursive_totals(),
+        }
+    }
+
+    /// The file itself, or all files in the directory and its subdirectories.
+    pub fn all_files(&self) -> Vec<&FileData> {
+        match self {
+            PathData::File(file) => vec![file],
+            PathData::Dir(dir) => dir.all_files(),
+        }
+    }
+
+    /// The skipped binary files in the directory and its subdirectories.
+    pub fn all_skipped_binary(&self) -> Vec<&SkippedFile> {
+        match self {
+            PathData::File(_) => vec![],
+            PathData::Dir(dir) => dir.all_skipped_binary(),
+        }
+    }
+
+    /// The excluded entries of the directory and its subdirectories.
+    pub fn all_excluded(&self) -> Vec<&ExcludedFile> {
+        match self {
+            PathData::File(_) => vec![],
+            PathData::Dir(dir) => dir.all_excluded(),
+        }
+    }
+
+    /// The errors that occurred in the directory and its subdirectories.
+    pub fn all_errors(&self) -> Vec<&FileError> {
+        match self {
+            PathData::File(_) => vec![],
+            PathData::Dir(dir) => dir.all_errors(),
+        }
+    }
+}
+
+/// The counts of a single file.
+#[derive(Debug)]
+pub struct FileData {
+    pub file_name: String,
+    /// The detected language, `None` if it is unknown
+    pub language: Option<&'static Language>,
+    /// Size of the contents in bytes
+    pub bytes: usize,
+    pub lines: usize,
+    /// Line feed characters, the lines as counted by `wc -l`
+    pub newlines: usize,
+    /// Unicode scalar values, including whitespace and line breaks
+    pub characters: usize,
+    /// Extended grapheme clusters, i.e. user-perceived characters
+    pub graphemes: usize,
+    /// Characters that are neither whitespace nor control characters
+    pub visible: usize,
+    /// Invalid UTF-8 sequences, each counted as one replacement character in `characters`
+    pub invalid: usize,
+    pub words: usize,
+    /// Lines containing code, including lines with trailing comments
+    pub code: usize,
+    /// Lines containing nothing but comments
+    pub comments: usize,
+    pub blanks: usize,
+    /// Whether the file looks like binary data, i.e. contains NUL bytes or mostly control characters
+    pub binary: bool,
+    /// The lines attributed to each author, sorted by lines, only set when counting with
+    /// [`Counter::by_author`]
+    pub authors: Vec<AuthorLines>,
+}
+
+/// The author of lines that are not committed yet.
+pub const NOT_COMMITTED: &str = "Not Committed Yet";
+
+/// The lines of one or more files last changed by an author.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthorLines {
+    pub name: String,
+    /// Empty for lines that are not committed yet
+    pub email: String,
+    pub lines: usize,
+}
+
+/// A file or directory that could not be counted.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: String,
+    pub error: Error,
+}
+
+/// A file or directory that was not counted because of a filter or an ignore file.
+#[derive(Debug)]
+pub struct ExcludedFile {
+    pub file_name: String,
+    pub reason: Exclusion,
+}
+
+/// A binary file that was not counted.
+#[derive(Debug)]
+pub struct SkippedFile {
+    pub file_name: String,
+    /// Size of the file in bytes
+    pub size: u64,
+}
+
+impl FileData {
+    /// Name of the detected language, or "Other" if it could not be detected.
+    pub fn language_name(&self) -> &'static str {
+        self.language.map_or("Other", |lang| lang.name)
+    }
+}
+
+/// Sums of the counts of a number of files.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Totals {
+    pub files: usize,
+    pub bytes: usize,
+    pub lines: usize,
+    pub characters: usize,
+    pub graphemes: usize,
+    pub visible: usize,
+    pub words: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+impl Totals {
+    pub fn of<'a>(files: impl IntoIterator<Item = &'a FileData>) -> Self {
+        let mut totals = Totals::default();
+        for file in files {
+            totals.files += 1;
+            totals.bytes += file.bytes;
+            totals.lines += file.lines;
+            totals.characters += file.characters;
+            totals.graphemes += file.graphemes;
+            totals.visible += file.visible;
+            totals.words += file.words;
+            totals.code += file.code;
+            totals.comments += file.comments;
+            totals.blanks += file.blanks;
+        }
+        totals
+    }
+}
+
+impl std::ops::AddAssign for Totals {
+    fn add_assign(&mut self, other: Totals) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+        self.characters += other.characters;
+        self.graphemes += other.graphemes;
+        self.visible += other.visible;
+        self.words += other.words;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+}
+
+/// Totals of all files of one language.
+#[derive(Debug, Default, Serialize)]
+pub struct LanguageSummary {
+    pub name: &'static str,
+    pub files: usize,
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+/// Groups the given files by language, sorted by language name.
+pub fn summarize_languages<'a>(
+    files: impl IntoIterator<Item = &'a FileData>,
+) -> Vec<LanguageSummary> {
+    let mut summary: BTreeMap<&'static str, LanguageSummary> = BTreeMap::new();
+    for file in files {
+        let name = file.language_name();
+        let entry = summary.entry(name).or_insert_with(|| LanguageSummary {
+            name,
+            ..Default::default()
+        });
+        entry.files += 1;
+        entry.lines += file.lines;
+        entry.code += file.code;
+        entry.comments += file.comments;
+        entry.blanks += file.blanks;
+    }
+    summary.into_values().collect()
+}
+
+/// Adds up the lines of every author over the given files, sorted by lines in descending order.
+pub fn summarize_authors<'a>(files: impl IntoIterator<Item = &'a FileData>) -> Vec<AuthorLines> {
+    let mut summary: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for author in files.into_iter().flat_map(|file| &file.authors) {
+        *summary.entry((&author.name, &author.email)).or_default() += author.lines;
+    }
+    sort_authors(
+        summary
+            .into_iter()
+            .map(|((name, email), lines)| AuthorLines {
+                name: name.to_owned(),
+                email: email.to_owned(),
+                lines,
+            })
+            .collect(),
+    )
+}
+
+/// Sorts authors by lines in descending order, and authors with the same lines by name.
+fn sort_authors(mut authors: Vec<AuthorLines>) -> Vec<AuthorLines> {
+    authors.sort_by(|a, b| {
+        b.lines
+            .cmp(&a.lines)
+            .then_with(|| (&a.name, &a.email).cmp(&(&b.name, &b.email)))
+    });
+    authors
+}
+
+/// The counts of a directory, sorted by name.
+#[derive(Debug)]
+pub struct DirData {
+    pub dir_name: String,
+    pub file_data: Vec<FileData>,
+    pub sub_dirs: Vec<DirData>,
+    pub skipped_binary: Vec<SkippedFile>,
+    /// Files and directories left out by the filters or ignore files
+    pub excluded: Vec<ExcludedFile>,
+    /// Files and directories that could not be counted
+    pub errors: Vec<FileError>,
+}
+
+impl DirData {
+    fn new(dir_name: impl Into<String>) -> Self {
+        Self {
+            dir_name: dir_name.into(),
+            file_data: vec![],
+            sub_dirs: vec![],
+            skipped_binary: vec![],
+            excluded: vec![],
+            errors: vec![],
+        }
+    }
+
+    fn push(&mut self, entry: Counted) {
+        match entry {
+            Counted::File(file) => self.file_data.push(file),
+            Counted::Dir(dir) => self.sub_dirs.push(dir),
+            Counted::SkippedBinary(file) => self.skipped_binary.push(file),
+            Counted::Excluded(file) => self.excluded.push(file),
+            Counted::Failed(error) => self.errors.push(error),
+        }
+    }
+
+    /// The subdirectory at the relative `path`, creating it and its parents if needed.
+    fn sub_dir_mut(&mut self, path: &std::path::Path) -> &mut DirData {
+        let mut dir = self;
+        let mut dir_path = std::path::PathBuf::new();
+        for component in path.components() {
+            if component == std::path::Component::CurDir {
+                continue;
+            }
+            dir_path.push(component);
+            let name = dir_path.to_string_lossy();
+            let index = match dir.sub_dirs.iter().position(|d| d.dir_name == name) {
+                Some(index) => index,
+                None => {
+                    dir.sub_dirs.push(DirData::new(name));
+                    dir.sub_dirs.len() - 1
+                }
+            };
+            dir = &mut dir.sub_dirs[index];
+        }
+        dir
+    }
+
+    /// Takes the errors of this directory and all of its subdirectories.
+    fn into_errors(self) -> Vec<FileError> {
+        let mut errors = self.errors;
+        for dir in self.sub_dirs {
+            errors.extend(dir.into_errors());
+        }
+        errors
+    }
+
+    /// Sorts the entries of this directory and all of its subdirectories by name.
+    fn sort(&mut self) {
+        self.file_data.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        self.skipped_binary
+            .sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        self.excluded.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        self.errors.sort_by(|a, b| a.path.cmp(&b.path));
+        self.sub_dirs.sort_by(|a, b| a.dir_name.cmp(&b.dir_name));
+        for dir in &mut self.sub_dirs {
+            dir.sort();
+        }
+    }
+
+    /// Totals of the files directly inside this directory.
+    pub fn direct_totals(&self) -> Totals {
+        Totals::of(&self.file_data)
+    }
+
+    /// Totals of the files in this directory and all of its subdirectories.
+    pub fn recursive_totals(&self) -> Totals {
+        let mut totals = self.direct_totals();
+        for dir in &self.sub_dirs {
+            totals += dir.recursive_totals();
+        }
+        totals
+    }
+
+    /// Collects the files of this directory and all of its subdirectories.
+    pub fn all_files(&self) -> Vec<&FileData> {
+        let mut files: Vec<&FileData> = self.file_data.iter().collect();
+        for dir in &self.sub_dirs {
+            files.extend(dir.all_files());
+        }
+        files
+    }
+
+    /// Collects the skipped binary files of this directory and all of its subdirectories.
+    pub fn all_skipped_binary(&self) -> Vec<&SkippedFile> {
+        let mut skipped: Vec<&SkippedFile> = self.skipped_binary.iter().collect();
+        for dir in &self.sub_dirs {
+            skipped.extend(dir.all_skipped_binary());
+        }
+        skipped
+    }
+
+    /// Collects the excluded entries of this directory and all of its subdirectories.
+    pub fn all_excluded(&self) -> Vec<&ExcludedFile> {
+        let mut excluded: Vec<&ExcludedFile> = self.excluded.iter().collect();
+        for dir in &self.sub_dirs {
+            excluded.extend(dir.all_excluded());
+        }
+        excluded
+    }
+
+    /// Collects the errors of this directory and all of its subdirectories.
+    pub fn all_errors(&self) -> Vec<&FileError> {
+        let mut errors: Vec<&FileError> = self.errors.iter().collect();
+        for dir in &self.sub_dirs {
+            errors.extend(dir.all_errors());
+        }
+        errors
+    }
+
+    /// Groups the files of this directory and all of its subdirectories by language.
+    pub fn language_summary(&self) -> Vec<LanguageSummary> {
+        summarize_languages(self.all_files())
+    }
+
+    /// Adds up the lines of every author in this directory and all of its subdirectories.
+    pub fn author_summary(&self) -> Vec<AuthorLines> {
+        summarize_authors(self.all_files())
+    }
+}
+
+/// Counts a single file. Returns `None` if the file is binary, unless `include_binary` is set.
+fn get_file_data(
+    path: impl Into<String>,
+    counter: &Counter,
+    include_binary: bool,
+) -> Result<Option<FileData>> {
+    let file_name: String = path.into();
+    let language = Language::detect(&file_name);
+    match counter::count_file(
+        &file_name,
+        language,
+        counter.word_mode,
+        counter.mmap,
+        include_binary,
+    )? {
+        Some(counts) => {
+            let mut file = counter.file_data(file_name, language, counts);
+            if counter.by_author {
+                file.authors = sort_authors(git::blame(&file.file_name, counter.skip_empty_lines)?);
+            }
+            Ok(Some(file))
+        }
+        None => Ok(None),
+    }
+}
+
+/// An entry of a directory after it has been counted.
+enum Counted {
+    File(FileData),
+    Dir(DirData),
+    SkippedBinary(SkippedFile),
+    Excluded(ExcludedFile),
+    Failed(FileError),
+}
+
+/// The position of a directory in a walk over a directory tree.
+#[derive(Clone)]
+struct Walk<'a> {
+    /// The directory the walk started in, filters match paths relative to it
+    root: &'a Path,
+    /// Device of the root, if the walk stays on its file system
+    device: Option<u64>,
+    /// The directories from the root down to the current one, if symbolic links are followed
+    ancestors: Vec<FileId>,
+    /// The files the walk is restricted to with [`Counter::git_files`]
+    selection: Option<&'a git::Selection>,
+}
+
+/// Device and inode number of a file.
+type FileId = (u64, u64);
+
+impl<'a> Walk<'a> {
+    fn new(root: &'a Path, counter: &Counter) -> Result<Self> {
+        let device = if counter.one_file_system {
+            file_id(&std::fs::metadata(root)?).map(|(device, _)| device)
+        } else {
+            None
+        };
+        Ok(Self {
+            root,
+            device,
+            ancestors: vec![],
+            selection: None,
+        })
+    }
+
+    /// The walk continuing in `dir`, one of the subdirectories of the current directory.
+    fn enter(&self, dir: &str, counter: &Counter) -> Result<Self> {
+        let mut walk = self.clone();
+        if counter.follow_symlinks {
+            walk.ancestors.extend(file_id(&std::fs::metadata(dir)?));
+        }
+        Ok(walk)
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &std::fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &std::fs::Metadata) -> Option<FileId> {
+    None
+}
+
+/// Counts the files of a directory, skipping hidden entries, everything excluded by the
+/// ignore files of the directory or its parents or by the [`Counter::filters`], and
+/// directories on other file systems with [`Counter::one_file_system`].
+///
+/// Entries are counted in parallel on the current rayon thread pool and sorted by name,
+/// so the result does not depend on the number of threads.
+///
+/// Entries that cannot be counted are recorded in [`DirData::errors`], unless
+/// [`Counter::fail_fast`] is set, in which case the first error is returned.
+fn get_dir_data(
+    dir_path: &str,
+    walk: &Walk,
+    counter: &Counter,
+    ignores: &Ignores,
+) -> Result<DirData> {
+    let mut dir_data = DirData::new(dir_path);
+    let ignores = ignores.enter(dir_path)?;
+    let walk = walk.enter(dir_path, counter)?;
+    let mut entries = vec![];
+    for entry in std::fs::read_dir(dir_path)? {
+        match entry {
+            Ok(e) => entries.push(e),
+            Err(err) if !counter.fail_fast => dir_data.errors.push(FileError {
+                path: dir_path.to_owned(),
+                error: err.into(),
+            }),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    entries.sort_by_key(|e| e.file_name());
+
+    let counted = entries
+        .par_iter()
+        .map(|e| match count_entry(e, &walk, counter, &ignores) {
+            Err(error) if !counter.fail_fast => Ok(Some(Counted::Failed(FileError {
+                path: e.path().to_string_lossy().into_owned(),
+                error,
+            }))),
+            counted => counted,
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for entry in counted.into_iter().flatten() {
+        dir_data.push(entry);
+    }
+    Ok(dir_data)
+}
+
+/// Counts a single entry of a directory, returning `None` if it is neither a file
+/// nor a directory to recurse into.
+fn count_entry(
+    e: &std::fs::DirEntry,
+    walk: &Walk,
+    counter: &Counter,
+    ignores: &Ignores,
+) -> Result<Option<Counted>> {
+    let mut metadata = e.metadata()?;
+    if e.file_name() == ".lcignore" {
+        return Ok(None);
+    }
+    if metadata.is_symlink() {
+        if !counter.follow_symlinks {
+            return Ok(None);
+        }
+        metadata = std::fs::metadata(e.path())?;
+    }
+    let path = match e.path().into_os_string().into_string() {
+        Ok(path) => path,
+        Err(path) => return Err(Error::LcInvalidPathError(path.into())),
+    };
+    let relative = Path::new(&path)
+        .strip_prefix(walk.root)
+        .unwrap_or(Path::new(&path));
+    let selected = walk.selection.is_none_or(|selection| {
+        if metadata.is_dir() {
+            selection.contains_dir(relative)
+        } else {
+            selection.contains_file(relative)
+        }
+    });
+    if !selected {
+        return Ok(None);
+    }
+    let hidden = (!counter.hidden && e.file_name().to_string_lossy().starts_with('.'))
+        .then_some(Exclusion::Hidden);
+    let exclusion = if metadata.is_dir() {
+        let depth = relative.components().count();
+        if !counter.recursive || counter.max_depth.is_some_and(|max| depth > max) {
+            return Ok(None);
+        }
+        let id = file_id(&metadata);
+        if id.is_some_and(|id| walk.ancestors.contains(&id)) {
+            return Err(Error::LcSymlinkLoopError(path.into()));
+        }
+        let other_device = walk
+            .device
+            .is_some_and(|device| id.is_some_and(|(dev, _)| dev != device));
+        hidden
+            .or_else(|| ignores.check(&path, true))
+            .or_else(|| counter.filters.check_dir(relative))
+            .or_else(|| other_device.then_some(Exclusion::OtherFileSystem))
+    } else if metadata.is_file() {
+        hidden
+            .or_else(|| ignores.check(&path, false))
+            .or_else(|| counter.filters.check_file(relative, metadata.len()))
+    } else {
+        return Ok(None);
+    };
+    if let Some(reason) = exclusion {
+        return Ok(Some(Counted::Excluded(ExcludedFile {
+            file_name: path,
+            reason,
+        })));
+    }
+    if metadata.is_dir() {
+        let data = get_dir_data(&path, walk, counter, ignores)?;
+        return Ok(Some(Counted::Dir(data)));
+    }
+    Ok(Some(
+        match get_file_data(&path, counter, counter.include_binary)? {
+            Some(data) => Counted::File(data),
+            None => Counted::SkippedBinary(SkippedFile {
+                file_name: path,
+                size: metadata.len(),
+            }),
+        },
+    ))
+}
+
+/// Counts a file given in a list of files, returning `None` if it is a directory.
+fn count_listed_file(path: &str, counter: &Counter) -> Result<Option<Counted>> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        return Ok(None);
+    }
+    let relative = Path::new(path).strip_prefix(".").unwrap_or(Path::new(path));
+    if let Some(reason) = counter.filters.check_file(relative, metadata.len()) {
+        return Ok(Some(Counted::Excluded(ExcludedFile {
+            file_name: path.to_owned(),
+            reason,
+        })));
+    }
+    Ok(Some(
+        match get_file_data(path, counter, counter.include_binary)? {
+            Some(data) => Counted::File(data),
+            None => Counted::SkippedBinary(SkippedFile {
+                file_name: path.to_owned(),
+                size: metadata.len(),
+            }),
+        },
+    ))
+}
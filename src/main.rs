@@ -1,223 +1,1127 @@
-use clap::Parser;
-use std::fs::File;
-use std::io::Read;
+mod config;
+mod csv;
+mod json;
+mod wc;
+
+use clap::{ArgEnum, Parser, Subcommand};
+use lc::diff::{DirDiff, FileDiff, LineDelta};
+use lc::filter::Filters;
+use lc::history::{Sample, Snapshot};
+use lc::{
+    summarize_authors, summarize_languages, AuthorLines, Counter, DirData, ExcludedFile, FileData,
+    FileError, GitFiles, LanguageSummary, PathData, SkippedFile, Totals, WordMode,
+};
+use std::io::{IsTerminal, Read, Write};
 use thiserror::Error;
+use wc::WcCounts;
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(
+    author,
+    version,
+    about,
+    args_override_self = true,
+    after_help = "Flags set in a config file can be turned off with --no-<FLAG>, e.g. --no-recursive."
+)]
 struct Args {
-    /// The path of the file or directory of which the lines should be counted
-    file_path: String,
+    /// The paths of the files or directories of which the lines should be counted, `-` for stdin.
+    /// Defaults to stdin if it is not a terminal, or the current directory otherwise
+    #[clap(value_name = "PATH")]
+    file_paths: Vec<String>,
+
+    /// Count exactly the files listed in FILE, one path per line, `-` for stdin
+    #[clap(long, value_name = "FILE", conflicts_with = "file-paths")]
+    files_from: Option<String>,
+
+    /// Separate the paths read by --files-from with NUL instead of newlines
+    #[clap(short = '0', long, takes_value = false, requires = "files-from")]
+    null: bool,
+
+    /// Count the paths as they are in the commit REF of their git repository, e.g. `HEAD~3` or `v1.0`
+    #[clap(long, value_name = "REF", conflicts_with = "files-from")]
+    rev: Option<String>,
+
+    /// Attribute the lines to the authors of their last change according to git blame,
+    /// merging identities with the mailmap of the repository
+    #[clap(long, takes_value = false, conflicts_with = "rev")]
+    by_author: bool,
+
+    /// Only count the files tracked in the index of their git repository
+    #[clap(long, takes_value = false, conflicts_with_all = &["rev", "files-from", "git-staged"])]
+    git_tracked: bool,
+
+    /// Only count the files with staged changes, reading them from the index, e.g. in a pre-commit hook
+    #[clap(
+        long,
+        takes_value = false,
+        conflicts_with_all = &["rev", "files-from", "by-author", "git-changed-since"]
+    )]
+    git_staged: bool,
+
+    /// Only count the files that changed since the commit REF, staged or not, like `git diff REF`
+    #[clap(
+        long,
+        value_name = "REF",
+        conflicts_with_all = &["rev", "files-from", "git-tracked"]
+    )]
+    git_changed_since: Option<String>,
 
     /// Skip empty lines
-    #[clap(short, takes_value = false)]
+    #[clap(short, long, takes_value = false, global = true)]
     skip_empty_lines: bool,
 
     /// Enable the recursive flag.
     /// line_counter will count lines in subdirectories recursively
-    #[clap(short, long, takes_value = false)]
+    #[clap(short, long, takes_value = false, global = true)]
     recursive: bool,
 
+    /// Only descend N levels of subdirectories, implies --recursive
+    #[clap(long, value_name = "N", global = true)]
+    max_depth: Option<usize>,
+
+    /// Count everything, but only print the tree down to N levels of subdirectories,
+    /// with the totals of the deeper ones rolled up into their parents
+    #[clap(long, value_name = "N")]
+    summarize_depth: Option<usize>,
+
+    /// Prints the number of characters, see --char-metrics
     #[clap(short, long, takes_value = false)]
     count_chars: bool,
 
+    /// Which character counts to print, comma separated. Implies --count-chars
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "METRIC",
+        use_value_delimiter = true,
+        multiple_occurrences = true
+    )]
+    char_metrics: Vec<CharMetric>,
+
     /// Prints the wordcount
     #[clap(short, long, takes_value = false)]
     words: bool,
 
-    /// To ignore files completely add a ".ignore.lc" file to the directory and write down the files that should be ignored.
-    ignored: Vec<String>,
+    /// How words are told apart
+    #[clap(long, arg_enum, value_name = "MODE", default_value = "whitespace")]
+    word_mode: WordModeArg,
+
+    /// Print the counts like GNU wc. COUNTS are the letters of the wc options to print,
+    /// e.g. `--wc=l` for `wc -l`, and default to `lwc`
+    #[clap(
+        long,
+        value_name = "COUNTS",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "lwc",
+        value_parser = WcCounts::parse
+    )]
+    wc: Option<WcCounts>,
+
+    /// How the results should be printed
+    #[clap(long, arg_enum, default_value = "text", global = true)]
+    format: OutputFormat,
+
+    /// What files and directories are sorted by
+    #[clap(long, arg_enum, value_name = "KEY", default_value = "name")]
+    sort: SortKey,
+
+    /// The sort order, ascending for names and descending for everything else by default
+    #[clap(long, arg_enum)]
+    order: Option<SortOrder>,
+
+    /// Only print the N largest files across all counted paths, by the --sort key or lines,
+    /// with their share of the total
+    #[clap(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Rank the subdirectories instead of the files with --top
+    #[clap(long, takes_value = false, requires = "top")]
+    top_dirs: bool,
+
+    /// Number of threads used for counting, 0 uses one thread per CPU
+    #[clap(short = 'j', long, default_value_t = 0, global = true)]
+    threads: usize,
+
+    /// Memory map files of 64 MiB or more instead of reading them in chunks.
+    /// lc crashes if a mapped file is truncated meanwhile, e.g. by log rotation
+    #[clap(long, takes_value = false)]
+    mmap: bool,
+
+    /// Count binary files instead of skipping them
+    #[clap(long, takes_value = false, global = true)]
+    include_binary: bool,
+
+    /// Only count files matching the glob, relative to the counted directory. Can be repeated
+    #[clap(long, value_name = "GLOB", multiple_occurrences = true, global = true)]
+    include: Vec<String>,
+
+    /// Skip files and directories matching the glob, relative to the counted directory.
+    /// Can be repeated
+    #[clap(long, value_name = "GLOB", multiple_occurrences = true, global = true)]
+    exclude: Vec<String>,
+
+    /// Only count files with one of these comma separated extensions
+    #[clap(
+        long,
+        value_name = "EXT",
+        use_value_delimiter = true,
+        multiple_occurrences = true,
+        global = true
+    )]
+    ext: Vec<String>,
+
+    /// Skip files smaller than SIZE, in bytes or with a K, M or G suffix
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, global = true)]
+    min_size: Option<u64>,
+
+    /// Skip files larger than SIZE, in bytes or with a K, M or G suffix
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, global = true)]
+    max_size: Option<u64>,
+
+    /// List the files and directories that were excluded, and why
+    #[clap(short, long, takes_value = false, global = true)]
+    verbose: bool,
+
+    /// Count hidden files and directories, whose names start with a `.`
+    #[clap(long, takes_value = false, global = true)]
+    hidden: bool,
+
+    /// Follow symbolic links instead of skipping them
+    #[clap(short = 'L', long, takes_value = false)]
+    follow_symlinks: bool,
+
+    /// Skip directories on other file systems than the counted directory
+    #[clap(short = 'x', long, takes_value = false)]
+    one_file_system: bool,
+
+    /// Stop at the first file that cannot be read instead of reporting it at the end
+    #[clap(long, takes_value = false, global = true)]
+    fail_fast: bool,
+
+    /// Apply the options of a profile defined in a config file
+    #[clap(long, global = true)]
+    profile: Option<String>,
+
+    /// Ignore all config files
+    #[clap(long, takes_value = false, global = true)]
+    no_config: bool,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare the line counts of two git revisions per file, directory and language
+    Diff {
+        /// The revision to compare from, e.g. the tag of the last release
+        old: String,
+
+        /// The revision to compare to
+        new: String,
+
+        /// The files or directories to compare, defaults to the current directory
+        #[clap(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+
+    /// Count the lines at the commits of the first-parent history of a git revision, oldest first
+    History {
+        /// The file or directory to count
+        #[clap(value_name = "PATH", default_value = ".")]
+        path: String,
+
+        /// The revision whose history is counted
+        #[clap(long, value_name = "REF", default_value = "HEAD")]
+        rev: String,
+
+        /// Which commits are counted
+        #[clap(long, arg_enum, default_value = "commit")]
+        sample: SampleArg,
+    },
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SampleArg {
+    /// Every commit
+    Commit,
+    /// The last commit of every day, in UTC
+    Day,
+    /// The last commit of every week, in UTC
+    Week,
+    /// Only tagged commits
+    Tag,
+}
+
+impl From<SampleArg> for Sample {
+    fn from(sample: SampleArg) -> Self {
+        match sample {
+            SampleArg::Commit => Sample::Commit,
+            SampleArg::Day => Sample::Day,
+            SampleArg::Week => Sample::Week,
+            SampleArg::Tag => Sample::Tag,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human readable tree followed by the totals
+    Text,
+    /// The whole tree as a JSON document, see the README for its schema
+    Json,
+    /// One comma separated row per file
+    Csv,
+    /// One tab separated row per file
+    Tsv,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CharMetric {
+    /// Size in bytes
+    Bytes,
+    /// Unicode scalar values, including whitespace and line breaks
+    Chars,
+    /// Extended grapheme clusters, i.e. user-perceived characters
+    Graphemes,
+    /// Characters that are neither whitespace nor control characters
+    Visible,
+}
+
+impl CharMetric {
+    fn value(self, totals: &Totals) -> usize {
+        match self {
+            CharMetric::Bytes => totals.bytes,
+            CharMetric::Chars => totals.characters,
+            CharMetric::Graphemes => totals.graphemes,
+            CharMetric::Visible => totals.visible,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CharMetric::Bytes => "bytes",
+            CharMetric::Chars => "chars",
+            CharMetric::Graphemes => "graphemes",
+            CharMetric::Visible => "visible",
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum WordModeArg {
+    /// Runs of characters between whitespace, like wc
+    Whitespace,
+    /// Unicode word boundaries (UAX #29), leaving out punctuation
+    Unicode,
+}
+
+impl From<WordModeArg> for WordMode {
+    fn from(mode: WordModeArg) -> Self {
+        match mode {
+            WordModeArg::Whitespace => WordMode::Whitespace,
+            WordModeArg::Unicode => WordMode::Unicode,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    Lines,
+    Chars,
+    Words,
+    /// Size in bytes
+    Size,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortKey {
+    /// The value of the totals that is sorted by, `None` when sorting by name.
+    fn value(self, totals: &Totals) -> Option<usize> {
+        match self {
+            SortKey::Name => None,
+            SortKey::Lines => Some(totals.lines),
+            SortKey::Chars => Some(totals.characters),
+            SortKey::Words => Some(totals.words),
+            SortKey::Size => Some(totals.bytes),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Lines => "lines",
+            SortKey::Chars => "chars",
+            SortKey::Words => "words",
+            SortKey::Size => "size",
+        }
+    }
 }
 
 impl Args {
-    /// Checks if a ".ignore.lc" file is within the directory, and adds them to the ignored_vec.
-    fn with_ignored(mut self) -> Result<Self> {
-        if !std::fs::metadata(&self.file_path)?.is_dir() {
-            return Ok(self);
-        }
-        for entry in std::fs::read_dir(&self.file_path)?.flatten() {
-            if entry.file_name() == ".lcignore" {
-                let mut f = File::open(entry.path())?;
-
-                let mut ignored = String::new();
-                f.read_to_string(&mut ignored)?;
-
-                self.ignored = ignored
-                    .lines()
-                    .map(|line| line.trim().to_string())
-                    .collect();
-                self.ignored.push(String::from(".lcignore"));
-            }
+    /// The paths to count, falling back to stdin or the current directory if none are given.
+    fn paths(&self) -> Vec<&str> {
+        if !self.file_paths.is_empty() {
+            self.file_paths.iter().map(String::as_str).collect()
+        } else if self.rev.is_some()
+            || self.git_files() != GitFiles::All
+            || std::io::stdin().is_terminal()
+        {
+            vec!["."]
+        } else {
+            vec![STDIN_PATH]
+        }
+    }
+
+    /// The files of git repositories to count.
+    fn git_files(&self) -> GitFiles {
+        if self.git_tracked {
+            GitFiles::Tracked
+        } else if self.git_staged {
+            GitFiles::Staged
+        } else if let Some(rev) = &self.git_changed_since {
+            GitFiles::ChangedSince(rev.clone())
+        } else {
+            GitFiles::All
         }
-        Ok(self)
+    }
+
+    /// The character counts to print, chars only for a plain `-c`.
+    fn char_metrics(&self) -> Vec<CharMetric> {
+        if !self.char_metrics.is_empty() {
+            self.char_metrics.clone()
+        } else if self.count_chars {
+            vec![CharMetric::Chars]
+        } else {
+            vec![]
+        }
+    }
+
+    fn descending(&self) -> bool {
+        match self.order {
+            Some(order) => order == SortOrder::Desc,
+            None => self.sort != SortKey::Name,
+        }
+    }
+
+    /// The counter configured by the command line options.
+    fn counter(&self) -> Result<Counter> {
+        let mut filters = Filters::new()
+            .extensions(&self.ext)
+            .min_size(self.min_size)
+            .max_size(self.max_size);
+        for glob in &self.include {
+            filters = filters.include(glob)?;
+        }
+        for glob in &self.exclude {
+            filters = filters.exclude(glob)?;
+        }
+        Ok(Counter::new()
+            .skip_empty_lines(self.skip_empty_lines)
+            .word_mode(match self.wc {
+                Some(_) => WordMode::Whitespace,
+                None => self.word_mode.into(),
+            })
+            .recursive(self.recursive || self.max_depth.is_some())
+            .max_depth(self.max_depth)
+            .threads(self.threads)
+            .mmap(self.mmap)
+            // wc counts every file
+            .include_binary(self.include_binary || self.wc.is_some())
+            .fail_fast(self.fail_fast)
+            .hidden(self.hidden)
+            .follow_symlinks(self.follow_symlinks)
+            .one_file_system(self.one_file_system)
+            .by_author(self.by_author)
+            .git_files(self.git_files())
+            .filters(filters))
     }
 }
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 enum Error {
-    #[error("Error occurred while reading file")]
-    LcIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    LcCountError(#[from] lc::Error),
+
+    #[error("Error occurred while writing output: {0}")]
+    LcOutputError(#[from] std::io::Error),
+
+    #[error("Error occurred while writing JSON: {0}")]
+    LcJsonError(#[from] serde_json::Error),
+
+    #[error("Invalid config file {path:?}: {message}")]
+    LcConfigError {
+        path: std::path::PathBuf,
+        message: String,
+    },
+
+    #[error("Could not read the file list {path}: {source}")]
+    LcFileListError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid glob: {0}")]
+    LcGlobError(#[from] globset::Error),
+
+    #[error("No config file defines the profile `{0}`")]
+    LcUnknownProfileError(String),
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
-    let args = Args::parse().with_ignored()?;
+/// Exit code used when some files could not be counted, as 1 is used for fatal errors
+/// and 2 by clap for invalid command lines.
+const EXIT_FILE_ERRORS: i32 = 3;
 
-    let file_metadata = std::fs::metadata(&args.file_path)?;
+/// The path standing for stdin.
+const STDIN_PATH: &str = "-";
 
-    if file_metadata.is_dir() {
-        if let Some(d_data) = get_dir_data(&args.file_path, &args)? {
-            print_dir(&d_data, &args);
-            println!("Total lines: {total}", total = d_data.total_lines());
-            println!(
-                "Total characters: {total}",
-                total = d_data.total_characters()
-            );
-            println!("Total Words: {total}", total = d_data.total_words());
+/// The file name stdin is counted as.
+const STDIN_NAME: &str = "<stdin>";
+
+fn main() {
+    if let Err(err) = run() {
+        // Whatever was printed before the error is still shown
+        let _ = std::io::stdout().flush();
+        eprintln!("Error: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
+    let args = config::parse_args()?;
+
+    let stdout = std::io::stdout().lock();
+
+    let counter = args.counter()?;
+    match &args.command {
+        Some(Command::Diff { old, new, paths }) => {
+            return diff_revs(old, new, paths, &counter, &args)
         }
-    } else {
-        let f_data = get_file_data(&args.file_path, args.skip_empty_lines)?;
-        print_file(&f_data, &args);
+        Some(Command::History { path, rev, sample }) => {
+            return history(rev, path, (*sample).into(), &counter, &args)
+        }
+        None => {}
+    }
+
+    let (mut results, errors) = match &args.files_from {
+        Some(list) => {
+            let paths = read_file_list(list, args.null)?;
+            (vec![PathData::Dir(counter.count_files(&paths)?)], vec![])
+        }
+        None => count_paths(&args.paths(), &counter, &args)?,
+    };
+    for data in &mut results {
+        if let PathData::Dir(dir) = data {
+            sort_dir(dir, args.sort, args.descending());
+        }
+    }
+
+    match (args.wc, args.format) {
+        (Some(counts), _) => {
+            let rows = wc_rows(&results, &args);
+            wc::write_rows(stdout, &rows, counts, rows.len() + errors.len())?
+        }
+        (None, OutputFormat::Text) => print_results(&results, &args),
+        (None, OutputFormat::Json) => json::write_paths(stdout, &results, &errors)?,
+        (None, OutputFormat::Csv) => csv::write_files(stdout, all_files(&results), ',')?,
+        (None, OutputFormat::Tsv) => csv::write_files(stdout, all_files(&results), '\t')?,
     }
 
+    let errors: Vec<&FileError> = errors
+        .iter()
+        .chain(results.iter().flat_map(PathData::all_errors))
+        .collect();
+    exit_on_errors(&errors)
+}
+
+/// Prints the files that could not be counted, if any, and exits with [`EXIT_FILE_ERRORS`].
+fn exit_on_errors(errors: &[&FileError]) -> Result<()> {
+    if !errors.is_empty() {
+        print_errors(errors);
+        std::io::stdout().flush()?;
+        std::process::exit(EXIT_FILE_ERRORS);
+    }
     Ok(())
 }
 
-fn print_file(file: &FileData, args: &Args) {
-    println!(
-        "{file_name} => {line_count} lines {chars} {word}",
-        // word = &file.words,
-        word = if args.words {
-            format!("and {} Words", &file.words)
-        } else {
-            "".to_owned()
-        },
-        file_name = &file.file_name,
-        line_count = file.lines,
-        chars = if args.count_chars {
-            format!("({chars} chars)", chars = file.characters)
-        } else {
-            "".to_owned()
+/// Runs `lc diff`, comparing each of the given paths between the revisions `old` and `new`.
+fn diff_revs(old: &str, new: &str, paths: &[String], counter: &Counter, args: &Args) -> Result<()> {
+    let paths = if paths.is_empty() {
+        vec![".".to_owned()]
+    } else {
+        paths.to_vec()
+    };
+    let mut diffs = vec![];
+    let mut errors = vec![];
+    for path in &paths {
+        match counter.diff_revs(old, new, path) {
+            Ok(diff) => diffs.push(diff),
+            Err(error) if paths.len() > 1 && !args.fail_fast => errors.push(FileError {
+                path: path.clone(),
+                error,
+            }),
+            Err(error) => return Err(error.into()),
         }
+    }
+
+    let stdout = std::io::stdout().lock();
+    match args.format {
+        OutputFormat::Text => print_diffs(&diffs, old, new),
+        OutputFormat::Json => json::write_diffs(stdout, old, new, &diffs, &errors)?,
+        OutputFormat::Csv => csv::write_file_diffs(stdout, changed_files(&diffs), ',')?,
+        OutputFormat::Tsv => csv::write_file_diffs(stdout, changed_files(&diffs), '\t')?,
+    }
+
+    let errors: Vec<&FileError> = errors
+        .iter()
+        .chain(diffs.iter().flat_map(DirDiff::all_errors))
+        .collect();
+    exit_on_errors(&errors)
+}
+
+/// Runs `lc history`, counting `path` at the sampled commits of the history of `rev`.
+fn history(rev: &str, path: &str, sample: Sample, counter: &Counter, args: &Args) -> Result<()> {
+    let snapshots = counter.history(rev, path, sample)?;
+    // Files that cannot be counted usually cannot be counted in many commits
+    let mut errors: Vec<&FileError> = snapshots
+        .iter()
+        .flat_map(|snapshot| &snapshot.errors)
+        .collect();
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+    errors.dedup_by(|a, b| a.path == b.path && a.error.to_string() == b.error.to_string());
+
+    let stdout = std::io::stdout().lock();
+    match args.format {
+        OutputFormat::Text => print_history(&snapshots),
+        OutputFormat::Json => json::write_history(stdout, rev, path, &snapshots, &errors)?,
+        OutputFormat::Csv => csv::write_history(stdout, &snapshots, ',')?,
+        OutputFormat::Tsv => csv::write_history(stdout, &snapshots, '\t')?,
+    }
+    exit_on_errors(&errors)
+}
+
+/// Prints one row per counted commit.
+fn print_history(snapshots: &[Snapshot]) {
+    println!(
+        "{:<12}{:<10}{:>8}{:>10}{:>10}{:>10}{:>10}  Tags",
+        "Date", "Commit", "Files", "Lines", "Code", "Comments", "Blanks"
     );
+    for snapshot in snapshots {
+        let totals = &snapshot.totals;
+        println!(
+            "{:<12}{:<10}{:>8}{:>10}{:>10}{:>10}{:>10}  {}",
+            snapshot.date(),
+            &snapshot.commit[..7],
+            totals.files,
+            totals.lines,
+            totals.code,
+            totals.comments,
+            totals.blanks,
+            snapshot.tags.join(" "),
+        );
+    }
 }
 
-fn print_dir(dir: &DirData, args: &Args) {
-    println!("{dir_name}: ", dir_name = &dir.dir_name);
-    for file in &dir.file_data {
-        print!("\t");
-        print_file(file, args);
+/// The files that differ between the revisions, in all of the compared paths.
+fn changed_files(diffs: &[DirDiff]) -> Vec<&FileDiff> {
+    diffs
+        .iter()
+        .flat_map(DirDiff::all_files)
+        .filter(|file| file.lines.is_changed())
+        .collect()
+}
+
+/// Prints the changed directories and files of `lc diff`, followed by the totals and a
+/// table by language.
+fn print_diffs(diffs: &[DirDiff], old: &str, new: &str) {
+    let mut rows = vec![];
+    for diff in diffs {
+        collect_diff_rows(diff, &mut rows);
     }
-    for dir in &dir.sub_dirs {
-        print!("\t\t");
-        print_dir(dir, args);
+    let mut totals = LineDelta::default();
+    for diff in diffs {
+        totals += diff.recursive_totals();
+    }
+    rows.push(("Total".to_owned(), totals));
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len() + 2)
+        .fold(16, usize::max);
+
+    println!("Lines from {old} to {new}:");
+    println!();
+    print_delta_row("Path", width, None);
+    for (name, delta) in &rows {
+        print_delta_row(name, width, Some(delta));
+    }
+    println!();
+    let languages = lc::diff::summarize_languages(diffs.iter().flat_map(DirDiff::all_files));
+    print_delta_row("Language", 16, None);
+    for lang in languages.iter().filter(|lang| lang.lines.is_changed()) {
+        print_delta_row(lang.name, 16, Some(&lang.lines));
     }
 }
 
-struct FileData {
-    file_name: String,
-    lines: usize,
-    characters: usize,
-    words: usize,
+/// Collects a row for the directory, its changed files and its changed subdirectories,
+/// with a trailing `/` on directory names.
+fn collect_diff_rows(dir: &DirDiff, rows: &mut Vec<(String, LineDelta)>) {
+    let totals = dir.recursive_totals();
+    if !totals.is_changed() {
+        return;
+    }
+    rows.push((format!("{}/", dir.dir_name.trim_end_matches('/')), totals));
+    for file in dir.files.iter().filter(|file| file.lines.is_changed()) {
+        rows.push((file.file_name.clone(), file.lines));
+    }
+    for sub_dir in &dir.sub_dirs {
+        collect_diff_rows(sub_dir, rows);
+    }
 }
 
-struct DirData {
-    dir_name: String,
-    file_data: Vec<FileData>,
-    sub_dirs: Vec<DirData>,
+/// Prints a row of the delta tables, or the header if `delta` is `None`.
+fn print_delta_row(name: &str, width: usize, delta: Option<&LineDelta>) {
+    match delta {
+        Some(delta) => println!(
+            "{name:<width$}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            delta.before,
+            delta.after,
+            delta.added,
+            delta.removed,
+            format!("{:+}", delta.net()),
+        ),
+        None => println!(
+            "{name:<width$}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "Before", "After", "Added", "Removed", "Net"
+        ),
+    }
 }
 
-impl DirData {
-    fn total_lines(&self) -> usize {
-        let mut total = 0;
-        for f in &self.file_data {
-            total += f.lines;
+/// Counts each of the given paths, stdin for `-`, or their contents in the commit given by `--rev`.
+///
+/// Returns the counted paths and, if several paths are given, the ones that could not be counted.
+fn count_paths(
+    paths: &[&str],
+    counter: &Counter,
+    args: &Args,
+) -> Result<(Vec<PathData>, Vec<FileError>)> {
+    let mut results = vec![];
+    let mut errors = vec![];
+    for path in paths {
+        let result = if let Some(rev) = &args.rev {
+            counter.count_rev(rev, path)
+        } else if *path == STDIN_PATH {
+            counter
+                .count_reader(STDIN_NAME, std::io::stdin().lock())
+                .map(PathData::File)
+        } else {
+            counter.count_path(path)
+        };
+        match result {
+            Ok(data) => results.push(data),
+            // Like wc, keep counting the other paths if one of many is missing
+            Err(error) if paths.len() > 1 && !args.fail_fast => errors.push(FileError {
+                path: path.to_string(),
+                error,
+            }),
+            Err(error) => return Err(error.into()),
         }
-        total
     }
+    Ok((results, errors))
+}
 
-    fn total_characters(&self) -> usize {
-        let mut total = 0;
-        for f in &self.file_data {
-            total += f.characters;
+/// Reads the paths listed in `list`, or stdin for `-`, separated by newlines or NUL bytes.
+fn read_file_list(list: &str, null: bool) -> Result<Vec<String>> {
+    let contents = if list == STDIN_PATH {
+        let mut contents = vec![];
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut contents)
+            .map(|_| contents)
+    } else {
+        std::fs::read(list)
+    };
+    let contents = contents.map_err(|err| Error::LcFileListError {
+        path: list.to_owned(),
+        source: err,
+    })?;
+    let separator = if null { b'\0' } else { b'\n' };
+    Ok(contents
+        .split(|&b| b == separator)
+        .map(|path| match path {
+            [path @ .., b'\r'] if !null => path,
+            path => path,
+        })
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect())
+}
+
+/// Parses a size in bytes, optionally followed by a binary `K`, `M` or `G` suffix.
+fn parse_size(size: &str) -> std::result::Result<u64, String> {
+    let lower = size.trim().to_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let factor = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        suffix => return Err(format!("unknown size suffix `{suffix}`")),
+    };
+    let value: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size `{size}`"))?;
+    value
+        .checked_mul(factor)
+        .ok_or_else(|| format!("size `{size}` is too large"))
+}
+
+/// Sorts the files and subdirectories of a directory and all of its subdirectories,
+/// directories by their recursive totals. Ties are sorted by name.
+fn sort_dir(dir: &mut DirData, key: SortKey, descending: bool) {
+    dir.file_data.sort_by(|a, b| {
+        compare(
+            (key.value(&Totals::of([a])), &a.file_name),
+            (key.value(&Totals::of([b])), &b.file_name),
+            descending,
+        )
+    });
+    let mut sub_dirs: Vec<(Option<usize>, DirData)> = dir
+        .sub_dirs
+        .drain(..)
+        .map(|dir| (key.value(&dir.recursive_totals()), dir))
+        .collect();
+    sub_dirs.sort_by(|(a, a_dir), (b, b_dir)| {
+        compare((*a, &a_dir.dir_name), (*b, &b_dir.dir_name), descending)
+    });
+    dir.sub_dirs = sub_dirs.into_iter().map(|(_, dir)| dir).collect();
+    for sub_dir in &mut dir.sub_dirs {
+        sort_dir(sub_dir, key, descending);
+    }
+}
+
+fn compare(
+    (a, a_name): (Option<usize>, &str),
+    (b, b_name): (Option<usize>, &str),
+    descending: bool,
+) -> std::cmp::Ordering {
+    let ordering = match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a_name.cmp(b_name),
+    };
+    let ordering = if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    };
+    ordering.then_with(|| a_name.cmp(b_name))
+}
+
+/// One `wc` row per counted file. Stdin is unnamed like in `wc` unless it is given as `-`.
+fn wc_rows<'a>(results: &'a [PathData], args: &Args) -> Vec<wc::Row<'a>> {
+    let mut rows = vec![];
+    for data in results {
+        match data {
+            PathData::File(file) if file.file_name == STDIN_NAME => {
+                let name = (!args.file_paths.is_empty()).then_some(STDIN_PATH);
+                rows.push(wc::Row::new(name, file, stdin_is_regular()));
+            }
+            PathData::File(file) => {
+                // The size of a file in a commit or the index is always known
+                let regular = args.rev.is_some()
+                    || args.git_staged
+                    || std::fs::metadata(&file.file_name).is_ok_and(|m| m.is_file());
+                rows.push(wc::Row::new(Some(&file.file_name), file, regular));
+            }
+            PathData::Dir(dir) => rows.extend(
+                dir.all_files()
+                    .into_iter()
+                    .map(|file| wc::Row::new(Some(&file.file_name), file, true)),
+            ),
         }
-        total
     }
+    rows
+}
+
+/// Whether stdin is redirected from a regular file rather than e.g. a pipe.
+#[cfg(unix)]
+fn stdin_is_regular() -> bool {
+    use std::os::fd::AsFd;
+    std::io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| std::fs::File::from(fd).metadata())
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+#[cfg(not(unix))]
+fn stdin_is_regular() -> bool {
+    false
+}
+
+fn all_files(results: &[PathData]) -> Vec<&FileData> {
+    results.iter().flat_map(PathData::all_files).collect()
+}
 
-    fn total_words(&self) -> usize {
-        let mut total = 0;
-        for f in &self.file_data {
-            total += f.words;
+/// Prints every counted path followed by the totals over all of them.
+fn print_results(results: &[PathData], args: &Args) {
+    match args.top {
+        Some(n) => print_top(results, n, args),
+        None => {
+            for data in results {
+                match data {
+                    PathData::File(file) => print_file(file, args),
+                    PathData::Dir(dir) => print_dir(dir, args, 0),
+                }
+            }
+        }
+    }
+    if results.len() > 1 || results.iter().any(|data| matches!(data, PathData::Dir(_))) {
+        let mut totals = Totals::default();
+        for data in results {
+            totals += data.recursive_totals();
         }
-        total
+        println!("Total lines: {total}", total = totals.lines);
+        println!("Total characters: {total}", total = totals.characters);
+        for metric in args.char_metrics() {
+            if metric != CharMetric::Chars {
+                println!("Total {}: {}", metric.name(), metric.value(&totals));
+            }
+        }
+        println!("Total Words: {total}", total = totals.words);
+    }
+    let skipped: Vec<&SkippedFile> = results
+        .iter()
+        .flat_map(PathData::all_skipped_binary)
+        .collect();
+    print_skipped_binary(&skipped);
+    if args.verbose {
+        let excluded: Vec<&ExcludedFile> =
+            results.iter().flat_map(PathData::all_excluded).collect();
+        print_excluded(&excluded);
+    }
+    println!();
+    print_language_summary(&summarize_languages(all_files(results)));
+    if args.by_author {
+        println!();
+        print_author_summary(&summarize_authors(all_files(results)));
     }
 }
 
-fn get_file_data(path: impl Into<String>, skip_empty_lines: bool) -> Result<FileData> {
-    let file_name: String = path.into();
+fn print_file(file: &FileData, args: &Args) {
+    println!(
+        "{file_name} => {line_count} lines {chars} {word}{binary}{authors}",
+        binary = if file.binary { " (binary)" } else { "" },
+        authors = top_authors(&file.authors),
+        // word = &file.words,
+        word = if args.words {
+            format!("and {} Words", &file.words)
+        } else {
+            "".to_owned()
+        },
+        file_name = &file.file_name,
+        line_count = file.lines,
+        chars = char_counts(&Totals::of([file]), args),
+    );
+}
+
+/// The selected character counts, e.g. `(120 bytes, 118 chars)`.
+fn char_counts(totals: &Totals, args: &Args) -> String {
+    let metrics = args.char_metrics();
+    if metrics.is_empty() {
+        return "".to_owned();
+    }
+    let counts: Vec<String> = metrics
+        .iter()
+        .map(|metric| format!("{} {}", metric.value(totals), metric.name()))
+        .collect();
+    format!("({})", counts.join(", "))
+}
 
-    let mut f = File::open(&file_name)?;
-    let mut s = String::new();
-    f.read_to_string(&mut s)?;
+/// The authors with the most lines, e.g. ` [Alice 600, Bob 200, 3 more]`.
+fn top_authors(authors: &[AuthorLines]) -> String {
+    const SHOWN: usize = 3;
+    if authors.is_empty() {
+        return "".to_owned();
+    }
+    let mut names: Vec<String> = authors
+        .iter()
+        .take(SHOWN)
+        .map(|author| format!("{} {}", author.name, author.lines))
+        .collect();
+    if authors.len() > SHOWN {
+        names.push(format!("{} more", authors.len() - SHOWN));
+    }
+    format!(" [{}]", names.join(", "))
+}
 
-    let lines;
-    let mut characters = 0;
-    let mut words = 0;
-    let empty_lines = s.lines().filter(|l| l.trim().is_empty()).count();
+/// Prints a directory with its totals, followed by its contents unless it is
+/// `--summarize-depth` levels below the counted directory.
+fn print_dir(dir: &DirData, args: &Args, depth: usize) {
+    let totals = dir.recursive_totals();
+    println!(
+        "{dir_name}: {line_count} lines in total {chars} {word}{authors}",
+        dir_name = &dir.dir_name,
+        authors = if args.by_author {
+            top_authors(&dir.author_summary())
+        } else {
+            "".to_owned()
+        },
+        line_count = totals.lines,
+        chars = char_counts(&totals, args),
+        word = if args.words {
+            format!("and {} Words", totals.words)
+        } else {
+            "".to_owned()
+        },
+    );
+    if args.summarize_depth.is_some_and(|max| depth >= max) {
+        return;
+    }
+    for file in &dir.file_data {
+        print!("\t");
+        print_file(file, args);
+    }
+    for dir in &dir.sub_dirs {
+        print!("\t\t");
+        print_dir(dir, args, depth + 1);
+    }
+}
 
-    lines = if skip_empty_lines {
-        s.lines().count() - empty_lines
-    } else {
-        s.lines().count()
+/// Prints the `n` largest files, or subdirectories with `--top-dirs`, with their share of the total.
+fn print_top(results: &[PathData], n: usize, args: &Args) {
+    let key = match args.sort {
+        SortKey::Name => SortKey::Lines,
+        key => key,
     };
+    let mut totals = Totals::default();
+    for data in results {
+        totals += data.recursive_totals();
+    }
+    let total = key.value(&totals).unwrap_or_default();
 
-    for char in s.chars() {
-        if char != '\n' || char != '\t' {
-            characters += 1;
-            if char.is_whitespace() || char.is_ascii_punctuation() || !char.is_alphabetic() {
-                words += 1;
+    let mut entries: Vec<(usize, &str)> = if args.top_dirs {
+        let mut dirs = vec![];
+        for data in results {
+            if let PathData::Dir(dir) = data {
+                collect_sub_dirs(dir, &mut dirs);
             }
         }
+        dirs.iter()
+            .map(|dir| (key.value(&dir.recursive_totals()), dir.dir_name.as_str()))
+            .map(|(value, name)| (value.unwrap_or_default(), name))
+            .collect()
+    } else {
+        all_files(results)
+            .into_iter()
+            .map(|file| {
+                (
+                    key.value(&Totals::of([file])).unwrap_or_default(),
+                    file.file_name.as_str(),
+                )
+            })
+            .collect()
+    };
+    entries.sort_by(|(a, a_name), (b, b_name)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    entries.truncate(n);
+
+    println!(
+        "Top {n} {kind} by {key}:",
+        kind = if args.top_dirs {
+            "directories"
+        } else {
+            "files"
+        },
+        key = key.name()
+    );
+    for (value, name) in entries {
+        let share = if total == 0 {
+            0.0
+        } else {
+            value as f64 * 100.0 / total as f64
+        };
+        println!("{value:>12} {share:>6.1}%  {name}");
     }
+}
 
-    let words = words - empty_lines;
+/// Collects all subdirectories of `dir` at any depth.
+fn collect_sub_dirs<'a>(dir: &'a DirData, dirs: &mut Vec<&'a DirData>) {
+    for sub_dir in &dir.sub_dirs {
+        dirs.push(sub_dir);
+        collect_sub_dirs(sub_dir, dirs);
+    }
+}
 
-    Ok(FileData {
-        file_name,
-        lines,
-        characters,
-        words,
-    })
+/// Prints the files that could not be counted to stderr.
+fn print_errors(errors: &[&FileError]) {
+    eprintln!();
+    eprintln!("{} error(s) occurred while counting:", errors.len());
+    for error in errors {
+        eprintln!("\t{}: {}", error.path, error.error);
+    }
 }
 
-fn get_dir_data(dir_path: &str, args: &Args) -> Result<Option<DirData>> {
-    let mut dir_data = DirData {
-        dir_name: dir_path.to_owned(),
-        file_data: vec![],
-        sub_dirs: vec![],
-    };
-    if args.ignored.contains(&dir_path.to_owned()) {
-        return Ok(None);
+fn print_skipped_binary(skipped: &[&SkippedFile]) {
+    if skipped.is_empty() {
+        return;
+    }
+    println!();
+    println!("Skipped binary files:");
+    for file in skipped {
+        println!("\t{} ({} bytes)", file.file_name, file.size);
     }
-    for entry in std::fs::read_dir(dir_path).into_iter().flatten() {
-        let e = if entry.is_ok() {
-            entry.unwrap()
+}
+
+fn print_excluded(excluded: &[&ExcludedFile]) {
+    if excluded.is_empty() {
+        return;
+    }
+    println!();
+    println!("Excluded:");
+    for file in excluded {
+        println!("\t{}: {}", file.file_name, file.reason);
+    }
+}
+
+/// Prints one row per author with their lines and share of all attributed lines.
+fn print_author_summary(summary: &[AuthorLines]) {
+    let total: usize = summary.iter().map(|author| author.lines).sum();
+    println!("{:<48}{:>10}{:>8}", "Author", "Lines", "Share");
+    for author in summary {
+        let name = if author.email.is_empty() {
+            author.name.clone()
         } else {
-            continue;
+            format!("{} <{}>", author.name, author.email)
         };
-        if args.recursive && e.metadata()?.is_dir() {
-            if let Some(data) = get_dir_data(e.path().to_str().unwrap(), args)? {
-                dir_data.sub_dirs.push(data);
-            }
-            continue;
-        }
-        if e.metadata()?.is_file() {
-            dir_data.file_data.push(get_file_data(
-                e.path().to_str().unwrap(),
-                args.skip_empty_lines,
-            )?);
-        }
+        let share = if total == 0 {
+            0.0
+        } else {
+            author.lines as f64 * 100.0 / total as f64
+        };
+        println!("{name:<48}{:>10}{share:>7.1}%", author.lines);
+    }
+}
+
+/// Prints one row per language with its file count and line breakdown.
+fn print_language_summary(summary: &[LanguageSummary]) {
+    println!(
+        "{:<16}{:>8}{:>10}{:>10}{:>10}{:>10}",
+        "Language", "Files", "Lines", "Code", "Comments", "Blanks"
+    );
+    for lang in summary {
+        println!(
+            "{:<16}{:>8}{:>10}{:>10}{:>10}{:>10}",
+            lang.name, lang.files, lang.lines, lang.code, lang.comments, lang.blanks
+        );
     }
-    Ok(Some(dir_data))
 }
@@ -0,0 +1,139 @@
+//! Output in the layout of GNU `wc`.
+
+use lc::FileData;
+use std::io::{self, Write};
+
+/// The counts selected with `--wc`, always printed in the order of `wc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WcCounts {
+    pub lines: bool,
+    pub words: bool,
+    pub chars: bool,
+    pub bytes: bool,
+}
+
+impl WcCounts {
+    /// Parses the letters of the `wc` options, e.g. `lw` for `wc -l -w`.
+    pub fn parse(letters: &str) -> Result<Self, String> {
+        let mut counts = WcCounts {
+            lines: false,
+            words: false,
+            chars: false,
+            bytes: false,
+        };
+        for letter in letters.chars() {
+            match letter {
+                'l' => counts.lines = true,
+                'w' => counts.words = true,
+                'm' => counts.chars = true,
+                'c' => counts.bytes = true,
+                _ => return Err(format!("unknown count `{letter}`, expected l, w, m or c")),
+            }
+        }
+        if letters.is_empty() {
+            return Err("no counts given, expected some of l, w, m and c".to_owned());
+        }
+        Ok(counts)
+    }
+
+    fn values(self, file: &Row) -> Vec<usize> {
+        [
+            (self.lines, file.newlines),
+            (self.words, file.words),
+            (self.chars, file.chars),
+            (self.bytes, file.bytes),
+        ]
+        .into_iter()
+        .filter_map(|(selected, value)| selected.then_some(value))
+        .collect()
+    }
+}
+
+/// A row of the output.
+#[derive(Default)]
+pub struct Row<'a> {
+    /// `None` for stdin when no paths are given
+    name: Option<&'a str>,
+    /// Whether the input is a regular file, whose size is known before counting
+    regular: bool,
+    newlines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+}
+
+impl<'a> Row<'a> {
+    pub fn new(name: Option<&'a str>, file: &FileData, regular: bool) -> Self {
+        Self {
+            name,
+            regular,
+            newlines: file.newlines,
+            words: file.words,
+            // wc skips invalid sequences instead of counting replacement characters
+            chars: file.characters - file.invalid,
+            bytes: file.bytes,
+        }
+    }
+}
+
+/// Writes one row per file, followed by a `total` row if more than one input was given.
+///
+/// `inputs` is the number of inputs including the ones that could not be counted.
+pub fn write_rows(
+    mut out: impl Write,
+    rows: &[Row],
+    counts: WcCounts,
+    inputs: usize,
+) -> io::Result<()> {
+    let width = column_width(rows, counts, inputs);
+    for row in rows {
+        write_row(&mut out, &counts.values(row), row.name, width)?;
+    }
+    if inputs > 1 {
+        let mut total = Row::default();
+        for row in rows {
+            total.newlines += row.newlines;
+            total.words += row.words;
+            total.chars += row.chars;
+            total.bytes += row.bytes;
+        }
+        write_row(&mut out, &counts.values(&total), Some("total"), width)?;
+    }
+    Ok(())
+}
+
+/// The width of the columns: enough digits for the total size of the regular files, at least
+/// 7 if any input is not a regular file, and 1 if a single count of a single input is printed.
+fn column_width(rows: &[Row], counts: WcCounts, inputs: usize) -> usize {
+    let selected = [counts.lines, counts.words, counts.chars, counts.bytes];
+    if inputs == 1 && selected.iter().filter(|&&s| s).count() == 1 {
+        return 1;
+    }
+    let minimum = if rows.iter().all(|row| row.regular) {
+        1
+    } else {
+        7
+    };
+    let size: usize = rows
+        .iter()
+        .filter(|row| row.regular)
+        .map(|row| row.bytes)
+        .sum();
+    size.to_string().len().max(minimum)
+}
+
+fn write_row(
+    out: &mut impl Write,
+    values: &[usize],
+    name: Option<&str>,
+    width: usize,
+) -> io::Result<()> {
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| format!("{value:>width$}"))
+        .collect();
+    match name {
+        Some(name) => writeln!(out, "{} {name}", values.join(" ")),
+        None => writeln!(out, "{}", values.join(" ")),
+    }
+}
@@ -0,0 +1,91 @@
+//! Counting of words in streamed text.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Runs of non-whitespace characters longer than this are segmented in pieces,
+/// so that text without whitespace can still be counted in constant memory.
+const MAX_RUN: usize = 64 * 1024;
+
+/// How words are told apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WordMode {
+    /// Like GNU `wc`: a word is a run of characters between whitespace that contains at
+    /// least one printable character. Unlike in Unicode, non-breaking spaces and the word
+    /// joiner U+2060 separate words but U+0085, U+2028 and U+2029 do not.
+    #[default]
+    Whitespace,
+    /// Unicode word boundaries (UAX #29): punctuation is not a word, `can't` and `3.14`
+    /// are one word each and every CJK ideograph is a word of its own.
+    Unicode,
+}
+
+/// Counts the words of text that is fed to it character by character.
+pub struct WordCounter {
+    mode: WordMode,
+    /// The current run of non-whitespace characters, only kept for [`WordMode::Unicode`]
+    run: String,
+    /// Whether the current run contains a printable character, for [`WordMode::Whitespace`]
+    in_word: bool,
+    words: usize,
+}
+
+impl WordCounter {
+    pub fn new(mode: WordMode) -> Self {
+        Self {
+            mode,
+            run: String::new(),
+            in_word: false,
+            words: 0,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        match self.mode {
+            WordMode::Whitespace => {
+                if is_wc_space(c) {
+                    self.end_word();
+                } else if !c.is_control() {
+                    self.in_word = true;
+                }
+            }
+            WordMode::Unicode => {
+                // Word boundaries are always next to whitespace, so each run can be
+                // segmented on its own
+                if c.is_whitespace() {
+                    self.end_run();
+                } else {
+                    self.run.push(c);
+                    if self.run.len() >= MAX_RUN {
+                        self.end_run();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> usize {
+        self.end_word();
+        self.end_run();
+        self.words
+    }
+
+    fn end_word(&mut self) {
+        if self.in_word {
+            self.words += 1;
+            self.in_word = false;
+        }
+    }
+
+    fn end_run(&mut self) {
+        if !self.run.is_empty() {
+            self.words += self.run.unicode_words().count();
+            self.run.clear();
+        }
+    }
+}
+
+/// Whitespace as GNU `wc` sees it in a UTF-8 locale, which also separates words at the
+/// word joiner U+2060 like at non-breaking spaces.
+fn is_wc_space(c: char) -> bool {
+    (c.is_whitespace() || c == '\u{2060}') && !matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+}
@@ -0,0 +1,184 @@
+//! Counts the revisions, index and blame of throwaway git repositories.
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+use lc::{Counter, GitFiles, PathData, NOT_COMMITTED};
+use std::path::{Path, PathBuf};
+
+/// A repository in a temporary directory, removed when dropped.
+struct TestRepo {
+    dir: PathBuf,
+    repo: Repository,
+}
+
+impl TestRepo {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("lc-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        Self { dir, repo }
+    }
+
+    fn path(&self) -> &str {
+        self.dir.to_str().unwrap()
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        std::fs::write(self.dir.join(file), contents).unwrap();
+    }
+
+    /// Stages every file of the working tree.
+    fn add_all(&self) {
+        let mut index = self.repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+    }
+
+    /// Commits the index and tags the commit with `tag`.
+    fn commit(&self, tag: &str) -> Oid {
+        let signature = Signature::now("Ada", "ada@example.com").unwrap();
+        let tree = self
+            .repo
+            .find_tree(self.repo.index().unwrap().write_tree().unwrap());
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit().unwrap());
+        let id = self
+            .repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                tag,
+                &tree.unwrap(),
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .unwrap();
+        self.repo
+            .tag_lightweight(tag, &self.repo.find_object(id, None).unwrap(), false)
+            .unwrap();
+        id
+    }
+
+    /// The number of loose objects in the object database.
+    fn object_count(&self) -> usize {
+        walk_files(&self.dir.join(".git").join("objects"))
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn walk_files(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk_files(&path)
+            } else {
+                1
+            }
+        })
+        .sum()
+}
+
+fn file<'a>(data: &'a PathData, name: &str) -> &'a lc::FileData {
+    data.all_files()
+        .into_iter()
+        .find(|file| file.file_name.ends_with(name))
+        .unwrap_or_else(|| panic!("{name} was not counted"))
+}
+
+#[test]
+fn staged_version_is_counted() {
+    let repo = TestRepo::new("staged");
+    repo.write("a.rs", "fn a() {}\n");
+    repo.add_all();
+    repo.commit("v1");
+    repo.write("a.rs", "fn a() {}\nfn b() {}\n");
+    repo.add_all();
+    repo.write("a.rs", "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n");
+    repo.write("untracked.rs", "fn e() {}\n");
+
+    let objects = repo.object_count();
+    let data = Counter::new()
+        .recursive(true)
+        .git_files(GitFiles::Staged)
+        .count_path(repo.path())
+        .unwrap();
+    assert_eq!(file(&data, "a.rs").lines, 2);
+    assert_eq!(data.all_files().len(), 1);
+    assert_eq!(
+        repo.object_count(),
+        objects,
+        "the object database was written to"
+    );
+
+    let data = Counter::new()
+        .recursive(true)
+        .count_path(repo.path())
+        .unwrap();
+    assert_eq!(file(&data, "a.rs").lines, 4);
+}
+
+#[test]
+fn delta_of_changed_file() {
+    let repo = TestRepo::new("delta");
+    repo.write("a.rs", "fn a() {}\nfn b() {}\nfn c() {}\n");
+    repo.write("b.rs", "fn unchanged() {}\n");
+    repo.add_all();
+    repo.commit("v1");
+    repo.write("a.rs", "fn a() {}\nfn c() {}\nfn d() {}\nfn e() {}\n");
+    repo.add_all();
+    repo.commit("v2");
+
+    let old = Counter::new()
+        .recursive(true)
+        .count_rev("v1", repo.path())
+        .unwrap();
+    assert_eq!(file(&old, "a.rs").lines, 3);
+
+    let diff = Counter::new()
+        .recursive(true)
+        .diff_revs("v1", "v2", repo.path())
+        .unwrap();
+    let delta = &diff
+        .files
+        .iter()
+        .find(|file| file.file_name.ends_with("a.rs"))
+        .unwrap()
+        .lines;
+    assert_eq!(
+        (delta.before, delta.after, delta.added, delta.removed),
+        (3, 4, 2, 1)
+    );
+}
+
+#[test]
+fn uncommitted_lines_are_not_attributed() {
+    let repo = TestRepo::new("blame");
+    repo.write("a.rs", "fn a() {}\nfn b() {}\n");
+    repo.add_all();
+    repo.commit("v1");
+    repo.write("a.rs", "fn a() {}\nfn b() {}\nfn c() {}\n");
+
+    let data = Counter::new()
+        .recursive(true)
+        .by_author(true)
+        .count_path(repo.path())
+        .unwrap();
+    let authors: Vec<_> = file(&data, "a.rs")
+        .authors
+        .iter()
+        .map(|author| (author.name.as_str(), author.email.as_str(), author.lines))
+        .collect();
+    assert_eq!(
+        authors,
+        [("Ada", "ada@example.com", 2), (NOT_COMMITTED, "", 1)]
+    );
+}
@@ -0,0 +1,113 @@
+//! Compares `lc --wc` with the output of GNU `wc` 9.1 in a UTF-8 locale on the files in `tests/wc`.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The `wc` options, the arguments and what `wc` printed for them.
+const FILE_CASES: &[(&str, &[&str], &str)] = &[
+    ("lwc", &["control.txt"], " 3  2 21 control.txt\n"),
+    ("l", &["control.txt"], "3 control.txt\n"),
+    ("w", &["control.txt"], "2 control.txt\n"),
+    ("m", &["control.txt"], "21 control.txt\n"),
+    ("c", &["control.txt"], "21 control.txt\n"),
+    ("lwc", &["empty.txt"], "0 0 0 empty.txt\n"),
+    ("l", &["empty.txt"], "0 empty.txt\n"),
+    ("w", &["empty.txt"], "0 empty.txt\n"),
+    ("m", &["empty.txt"], "0 empty.txt\n"),
+    ("c", &["empty.txt"], "0 empty.txt\n"),
+    ("lwc", &["invalid-utf8.txt"], " 2  4 33 invalid-utf8.txt\n"),
+    ("l", &["invalid-utf8.txt"], "2 invalid-utf8.txt\n"),
+    ("w", &["invalid-utf8.txt"], "4 invalid-utf8.txt\n"),
+    ("m", &["invalid-utf8.txt"], "26 invalid-utf8.txt\n"),
+    ("c", &["invalid-utf8.txt"], "33 invalid-utf8.txt\n"),
+    ("lwc", &["large.txt"], "  7381  36595 200001 large.txt\n"),
+    ("l", &["large.txt"], "7381 large.txt\n"),
+    ("w", &["large.txt"], "36595 large.txt\n"),
+    ("m", &["large.txt"], "153669 large.txt\n"),
+    ("c", &["large.txt"], "200001 large.txt\n"),
+    ("lwc", &["no-trailing-newline.txt"], " 1  6 38 no-trailing-newline.txt\n"),
+    ("l", &["no-trailing-newline.txt"], "1 no-trailing-newline.txt\n"),
+    ("w", &["no-trailing-newline.txt"], "6 no-trailing-newline.txt\n"),
+    ("m", &["no-trailing-newline.txt"], "38 no-trailing-newline.txt\n"),
+    ("c", &["no-trailing-newline.txt"], "38 no-trailing-newline.txt\n"),
+    ("lwc", &["only-newlines.txt"], "4 0 4 only-newlines.txt\n"),
+    ("l", &["only-newlines.txt"], "4 only-newlines.txt\n"),
+    ("w", &["only-newlines.txt"], "0 only-newlines.txt\n"),
+    ("m", &["only-newlines.txt"], "4 only-newlines.txt\n"),
+    ("c", &["only-newlines.txt"], "4 only-newlines.txt\n"),
+    ("lwc", &["unicode.txt"], "  4  15 145 unicode.txt\n"),
+    ("l", &["unicode.txt"], "4 unicode.txt\n"),
+    ("w", &["unicode.txt"], "15 unicode.txt\n"),
+    ("m", &["unicode.txt"], "109 unicode.txt\n"),
+    ("c", &["unicode.txt"], "145 unicode.txt\n"),
+    ("lwc", &["whitespace.txt"], " 4  7 57 whitespace.txt\n"),
+    ("l", &["whitespace.txt"], "4 whitespace.txt\n"),
+    ("w", &["whitespace.txt"], "7 whitespace.txt\n"),
+    ("m", &["whitespace.txt"], "57 whitespace.txt\n"),
+    ("c", &["whitespace.txt"], "57 whitespace.txt\n"),
+    ("lwc", &["control.txt", "empty.txt", "invalid-utf8.txt", "large.txt", "no-trailing-newline.txt", "only-newlines.txt", "unicode.txt", "whitespace.txt"], "     3      2     21 control.txt\n     0      0      0 empty.txt\n     2      4     33 invalid-utf8.txt\n  7381  36595 200001 large.txt\n     1      6     38 no-trailing-newline.txt\n     4      0      4 only-newlines.txt\n     4     15    145 unicode.txt\n     4      7     57 whitespace.txt\n  7399  36629 200299 total\n"),
+    ("l", &["control.txt", "empty.txt", "invalid-utf8.txt", "large.txt", "no-trailing-newline.txt", "only-newlines.txt", "unicode.txt", "whitespace.txt"], "     3 control.txt\n     0 empty.txt\n     2 invalid-utf8.txt\n  7381 large.txt\n     1 no-trailing-newline.txt\n     4 only-newlines.txt\n     4 unicode.txt\n     4 whitespace.txt\n  7399 total\n"),
+    ("wm", &["empty.txt", "unicode.txt"], "  0   0 empty.txt\n 15 109 unicode.txt\n 15 109 total\n"),
+    ("lwc", &["word-joiner.txt"], " 3  8 32 word-joiner.txt\n"),
+    ("l", &["word-joiner.txt"], "3 word-joiner.txt\n"),
+    ("w", &["word-joiner.txt"], "8 word-joiner.txt\n"),
+    ("m", &["word-joiner.txt"], "19 word-joiner.txt\n"),
+    ("c", &["word-joiner.txt"], "32 word-joiner.txt\n"),
+    ("lwmc", &["large.txt", "invalid-utf8.txt"], "  7381  36595 153669 200001 large.txt\n     2      4     26     33 invalid-utf8.txt\n  7383  36599 153695 200034 total\n"),
+];
+const STDIN_CASES: &[(&str, &[&str], &str)] = &[
+    ("lwc", &[], "      4      15     145\n"),
+    ("w", &[], "15\n"),
+    ("lwc", &["-"], "      4      15     145 -\n"),
+    ("lwc", &["-", "large.txt"], "      4      15     145 -\n   7381   36595  200001 large.txt\n   7385   36610  200146 total\n"),
+];
+
+/// Runs `lc --wc` in the corpus directory, piping `stdin` into it.
+fn lc_wc(counts: &str, args: &[&str], stdin: &[u8]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lc"))
+        .current_dir(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("wc"),
+        )
+        .arg("--no-config")
+        .arg(format!("--wc={counts}"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("lc can be started");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(stdin)
+        .expect("stdin can be written");
+    let output = child.wait_with_output().expect("lc runs");
+    assert!(output.status.success(), "lc --wc={counts} {args:?} failed");
+    String::from_utf8(output.stdout).expect("output is UTF-8")
+}
+
+#[test]
+fn files_match_wc() {
+    for (counts, files, expected) in FILE_CASES {
+        assert_eq!(
+            lc_wc(counts, files, b""),
+            *expected,
+            "wc -{counts} {files:?}"
+        );
+    }
+}
+
+#[test]
+fn stdin_matches_wc() {
+    let stdin = include_bytes!("wc/unicode.txt");
+    for (counts, args, expected) in STDIN_CASES {
+        assert_eq!(
+            lc_wc(counts, args, stdin),
+            *expected,
+            "wc -{counts} {args:?}"
+        );
+    }
+}